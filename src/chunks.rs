@@ -0,0 +1,114 @@
+use {
+    crate::{
+        parse::{Error, Input, ParseResult},
+        CtrlChars::CR,
+        Headers, SinglePartBody,
+    },
+    nom::{
+        bytes::streaming::{take, take_till},
+        character::streaming::{crlf, hex_digit1},
+        error::context,
+        sequence::terminated,
+        Err,
+    },
+    std::str::from_utf8,
+};
+
+/**
+ * A body sent with *TRANSFER-ENCODING: chunked*, decoded into its payload and trailers.
+ */
+#[derive(Debug)]
+pub struct ChunkedBody {
+    pub body: SinglePartBody,
+    pub trailers: Headers,
+}
+
+impl ChunkedBody {
+    /**
+     * Parse a sequence of chunks, terminated by the last (zero-sized) chunk and an optional trailer section.
+     */
+    pub fn parse(i: Input) -> ParseResult<Self> {
+        context("Chunked Body", |mut i: Input| {
+            let mut data = Vec::new();
+
+            loop {
+                let (rest, chunk) = Self::parse_chunk(i)?;
+                i = rest;
+
+                match chunk {
+                    Some(chunk) => data.extend_from_slice(chunk),
+                    None => break,
+                }
+            }
+
+            let (i, trailers) = Headers::parse(i)?;
+            let res = Self {
+                body: SinglePartBody { data },
+                trailers,
+            };
+
+            Ok((i, res))
+        })(i)
+    }
+
+    /**
+     * Parse a single chunk, returning its data or None if this was the last chunk.
+     */
+    fn parse_chunk(i: Input) -> ParseResult<Option<Input>> {
+        context("Chunk", |i| {
+            let (i, size) = Self::parse_chunk_header(i)?;
+
+            if size == 0 {
+                return Ok((i, None));
+            }
+
+            let (i, data) = terminated(take(size), crlf)(i)?;
+
+            Ok((i, Some(data)))
+        })(i)
+    }
+
+    /**
+     * Parse the line that starts a chunk, returning the size of its data.
+     * Chunk extensions are accepted but ignored.
+     */
+    pub(crate) fn parse_chunk_header(i: Input) -> ParseResult<usize> {
+        let (i, size) = Self::parse_chunk_size(i)?;
+        let (i, _extensions) = terminated(take_till(|c| c == CR as u8), crlf)(i)?;
+
+        Ok((i, size))
+    }
+
+    fn parse_chunk_size(i: Input) -> ParseResult<usize> {
+        context("Chunk Size", |i| {
+            let (rest, size) = hex_digit1(i)?;
+            let size = from_utf8(size)
+                .ok()
+                .and_then(|size| usize::from_str_radix(size, 16).ok())
+                .ok_or_else(|| {
+                    Err::Failure(Error::custom(i, "Chunk size is out of range.".to_string()))
+                })?;
+
+            Ok((rest, size))
+        })(i)
+    }
+}
+
+#[test]
+fn test_parse_chunked_body() {
+    let mut body = Vec::new();
+
+    body.extend_from_slice(b"4\r\nWiki\r\n");
+    body.extend_from_slice(b"6;name=value\r\npedia \r\n");
+    body.extend_from_slice(b"E\r\nin \r\n\r\nchunks.\r\n");
+    body.extend_from_slice(b"0\r\n");
+    body.extend_from_slice(b"Expires: never\r\n");
+    body.extend_from_slice(b"\r\n");
+
+    let (rest, chunked) = ChunkedBody::parse(&body).unwrap();
+
+    assert!(rest.is_empty());
+    assert_eq!(chunked.body.data, b"Wikipedia in \r\n\r\nchunks.");
+    assert_eq!(chunked.trailers.get("expires"), Some(&"never".to_string()));
+    assert!(ChunkedBody::parse(b"4\r\nWi").is_err());
+}
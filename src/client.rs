@@ -0,0 +1,387 @@
+use {
+    crate::{
+        connection::{default_tls_config, Connection},
+        connector::{default_connector, Connector},
+        cookie::CookieJar,
+        framing::BodyLength,
+        timeout::Timeouts,
+        CommonHeaders, Error, Headers, Protocol, Proxy, Request, Response, ResponseStatus,
+        StatusCode, StreamingResponse,
+    },
+    rustls::ClientConfig,
+    std::{
+        collections::HashMap,
+        hash::{Hash, Hasher},
+        io,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+};
+
+/**
+ * Idle connections are pooled by scheme, host, port, the proxy they go through and the connector that opened them.
+ */
+type PoolKey = (Protocol, String, u16, Option<Proxy>, Option<ConnectorId>);
+
+/**
+ * A connector compared by identity, so that a connection is only reused for requests with the connector that opened it.
+ */
+#[derive(Debug, Clone)]
+struct ConnectorId(Arc<dyn Connector>);
+
+impl ConnectorId {
+    fn addr(&self) -> *const () {
+        Arc::as_ptr(&self.0) as *const ()
+    }
+}
+
+impl PartialEq for ConnectorId {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr() == other.addr()
+    }
+}
+
+impl Eq for ConnectorId {}
+
+impl Hash for ConnectorId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state)
+    }
+}
+
+/**
+ * A client that keeps connections alive between requests and reuses them.
+ */
+pub struct Client {
+    pool: Mutex<HashMap<PoolKey, Vec<Connection>>>,
+    tls_config: Arc<ClientConfig>,
+    max_idle_per_host: usize,
+    timeouts: Timeouts,
+    cookies: Option<Arc<CookieJar>>,
+    connector: Option<Arc<dyn Connector>>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            pool: Mutex::new(HashMap::new()),
+            tls_config: default_tls_config(),
+            max_idle_per_host: 8,
+            timeouts: Timeouts::default(),
+            cookies: None,
+            connector: None,
+        }
+    }
+
+    /**
+     * Set the maximum number of idle connections kept per scheme, host and port. The default is 8.
+     */
+    pub fn max_idle_per_host(&mut self, max: usize) -> &mut Self {
+        self.max_idle_per_host = max;
+        self
+    }
+
+    /**
+     * Use a custom TLS configuration, e.g. with additional trusted certificates.
+     */
+    pub fn tls_config(&mut self, config: Arc<ClientConfig>) -> &mut Self {
+        self.tls_config = config;
+        self
+    }
+
+    /**
+     * Open connections with *connector* instead of the default of TLS over TCP, ignoring proxy settings.
+     * It is used in place of any connector set on a request; without it, requests use their own.
+     */
+    pub fn connector(&mut self, connector: Arc<dyn Connector>) -> &mut Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /**
+     * Store the cookies that servers set in *jar*, and send them with later requests they match.
+     * Requests that set their own *COOKIE* header are sent without the jar's cookies.
+     */
+    pub fn cookie_jar(&mut self, jar: Arc<CookieJar>) -> &mut Self {
+        self.cookies = Some(jar);
+        self
+    }
+
+    /**
+     * Set the connect timeout for requests that do not set their own.
+     */
+    pub fn connect_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeouts.connect = Some(timeout);
+        self
+    }
+
+    /**
+     * Set the read timeout for requests that do not set their own.
+     */
+    pub fn read_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeouts.read = Some(timeout);
+        self
+    }
+
+    /**
+     * Set the total timeout for requests that do not set their own.
+     */
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeouts.total = Some(timeout);
+        self
+    }
+
+    /**
+     * Send a request over a pooled connection, following redirects if the request has a redirect policy.
+     * Timeouts set on the request take precedence over those set on the client.
+     */
+    pub fn send(&self, req: &Request) -> Result<Response, Error> {
+        self.send_streaming(req)?.collect()
+    }
+
+    /**
+     * Send a request like *send*, but return as soon as the headers of the response have been read.
+     * The body is read on demand, and the connection is returned to the pool once it has been read to its end.
+     */
+    pub fn send_streaming(&self, req: &Request) -> Result<StreamingResponse<'_>, Error> {
+        let timeouts = req.timeouts.or(&self.timeouts);
+        let deadline = timeouts.deadline();
+        let send = |req: &Request| {
+            self.send_once(req, &timeouts, deadline)?
+                .authenticate(req, |req| self.send_once(req, &timeouts, deadline))
+        };
+        let res = send(req)?;
+        let res = match &req.redirect {
+            Some(policy) => res.follow_redirects(req, policy, send)?,
+            None => res,
+        };
+
+        res.decode_for(req)
+    }
+
+    fn send_once(
+        &self,
+        req: &Request,
+        timeouts: &Timeouts,
+        deadline: Option<Instant>,
+    ) -> Result<StreamingResponse<'_>, Error> {
+        let key = self.pool_key(req)?;
+        let connector = match &key.4 {
+            Some(ConnectorId(connector)) => connector.clone(),
+            None => default_connector(key.3.clone(), self.tls_config.clone()),
+        };
+        // A streamed body can only be sent once, so such requests cannot be retried.
+        let retry = req.method.is_idempotent() && req.body_reader.is_none();
+        let url = req.url();
+        let mut extra = Headers::new();
+        let absolute_form = req.forward_through(key.3.as_ref(), &mut extra);
+
+        if let (Some(jar), Some(url)) = (&self.cookies, &url) {
+            match jar.header_value(url) {
+                Some(cookies) if !req.headers.contains_key(&CommonHeaders::Cookie.to_string()) => {
+                    extra.insert(&CommonHeaders::Cookie.to_string(), &cookies)
+                }
+                _ => {}
+            }
+        }
+
+        loop {
+            let (mut conn, reused) = match self.checkout(&key) {
+                Some(mut conn) => {
+                    conn.limit(timeouts.read, deadline);
+                    (conn, true)
+                }
+                None => (
+                    Connection::connect(
+                        connector.as_ref(),
+                        key.0,
+                        &key.1,
+                        key.2,
+                        timeouts,
+                        deadline,
+                    )?,
+                    false,
+                ),
+            };
+
+            let res = req
+                .write_with(&mut conn, &extra, absolute_form)
+                .and_then(|_| StreamingResponse::read_head(conn, &req.method));
+
+            match res {
+                Ok(mut res) => {
+                    if let (Some(jar), Some(url)) = (&self.cookies, &url) {
+                        jar.store(url, &res.headers);
+                    }
+                    if Self::is_persistent(req, &res.status, &res.headers) {
+                        res.on_done(move |conn| self.checkin(key, conn));
+                    }
+                    return Ok(res);
+                }
+                // The server may have closed an idle connection; retry on a fresh one if that is safe.
+                Err(e) if reused && retry && Self::is_stale(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /**
+     * The number of idle connections currently pooled.
+     */
+    pub fn idle_connections(&self) -> usize {
+        self.pool.lock().unwrap().values().map(Vec::len).sum()
+    }
+
+    fn pool_key(&self, req: &Request) -> Result<PoolKey, Error> {
+        let host = req.host.as_ref().ok_or(Error::MissingHost)?;
+        let port = req.port.unwrap_or_else(|| req.protocol.default_port());
+
+        let connector = self.connector.as_ref().or(req.connector.as_ref());
+        let proxy = match connector {
+            Some(_) => None,
+            None => req.proxy.resolve(req.protocol, host),
+        };
+        let connector = connector.cloned().map(ConnectorId);
+
+        Ok((
+            req.protocol,
+            host.to_ascii_lowercase(),
+            port,
+            proxy,
+            connector,
+        ))
+    }
+
+    fn checkout(&self, key: &PoolKey) -> Option<Connection> {
+        self.pool.lock().unwrap().get_mut(key).and_then(Vec::pop)
+    }
+
+    fn checkin(&self, key: PoolKey, conn: Connection) {
+        let mut pool = self.pool.lock().unwrap();
+        let idle = pool.entry(key).or_default();
+
+        if idle.len() < self.max_idle_per_host {
+            idle.push(conn);
+        }
+    }
+
+    /**
+     * Whether the connection can be reused after this exchange, per RFC 7230 section 6.3.
+     */
+    fn is_persistent(req: &Request, status: &ResponseStatus, headers: &Headers) -> bool {
+        let has_token = |value: Option<&String>, token: &str| {
+            value.is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|option| option.trim().eq_ignore_ascii_case(token))
+            })
+        };
+        let connection = CommonHeaders::Connection.to_string();
+
+        if has_token(req.headers.get(&connection), "close")
+            || has_token(headers.get(&connection), "close")
+        {
+            return false;
+        }
+
+        if status.status_code == StatusCode::SwitchingProtocols {
+            return false;
+        }
+
+        // Without framing, the body ends when the server closes the connection.
+        match BodyLength::of_response(status.status_code, headers, &req.method) {
+            Ok(BodyLength::UntilClose) | Err(_) => return false,
+            _ => {}
+        }
+
+        status.protocol_version != "HTTP/1.0" || has_token(headers.get(&connection), "keep-alive")
+    }
+
+    fn is_stale(e: &Error) -> bool {
+        match e {
+            Error::ConnectionClosed => true,
+            Error::Io(e) => matches!(
+                e.kind(),
+                io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+            ),
+            _ => false,
+        }
+    }
+}
+
+#[test]
+fn test_reuse_connections() -> Result<(), Error> {
+    use {crate::read_request, std::io::Write, std::net::TcpListener, std::thread};
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+
+    // Accepts a single connection, so the second request must reuse it.
+    let server = thread::spawn(move || {
+        let (mut socket, _) = listener.accept().unwrap();
+
+        for body in &["first", "second"] {
+            read_request(&mut socket);
+            write!(
+                socket,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+
+    let client = Client::new();
+    let mut req = Request::default();
+
+    req.host("127.0.0.1").port(port).keep_alive();
+
+    for expected in &["first", "second"] {
+        let res = client.send(&req)?;
+
+        assert_eq!(res.body.unwrap().as_bytes(), expected.as_bytes());
+        assert_eq!(client.idle_connections(), 1);
+    }
+
+    server.join().unwrap();
+    Ok(())
+}
+
+#[test]
+fn test_client_cookies() -> Result<(), Error> {
+    use crate::{serve, RedirectPolicy};
+
+    better_panic::install();
+
+    let (port, requests) = serve(vec![
+        "HTTP/1.1 302 Found\r\nLocation: /home\r\nSet-Cookie: sid=abc; Path=/\r\n\
+            Connection: close\r\nContent-Length: 0\r\n\r\n"
+            .to_string(),
+        "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n".to_string(),
+    ]);
+    let jar = Arc::new(CookieJar::new());
+    let mut client = Client::new();
+    let mut req = Request::default();
+
+    client.cookie_jar(jar.clone());
+    req.path("/login")
+        .host("127.0.0.1")
+        .port(port)
+        .redirect(RedirectPolicy::default());
+
+    assert_eq!(client.send(&req)?.status.status_code, StatusCode::Success);
+    assert!(!requests.recv().unwrap().contains("COOKIE"));
+    assert!(requests.recv().unwrap().contains("COOKIE: sid=abc\r\n"));
+    assert_eq!(jar.cookies()[0].domain, "127.0.0.1");
+
+    Ok(())
+}
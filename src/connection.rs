@@ -0,0 +1,134 @@
+use {
+    crate::{
+        connector::{Connector, Transport},
+        timeout::{is_timeout, Timeout, Timeouts},
+        Error, Protocol,
+    },
+    rustls::ClientConfig,
+    std::{
+        io::{self, Read, Write},
+        sync::Arc,
+        time::{Duration, Instant},
+    },
+    webpki_roots::TLS_SERVER_ROOTS,
+};
+
+/**
+ * An open connection to a server, over a stream from a *Connector*.
+ * Reads and writes time out according to the limits set with *Connection::limit*.
+ */
+pub struct Connection {
+    stream: Box<dyn Transport>,
+    read_timeout: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl Connection {
+    /**
+     * Open a connection to *host* on *port* with *connector*.
+     * Connecting fails with Error::Timeout if it takes longer than *timeouts* or *deadline* allow.
+     */
+    pub fn connect(
+        connector: &dyn Connector,
+        protocol: Protocol,
+        host: &str,
+        port: u16,
+        timeouts: &Timeouts,
+        deadline: Option<Instant>,
+    ) -> Result<Self, Error> {
+        let stream = connector.connect(protocol, host, port, timeouts, deadline)?;
+
+        Ok(Self::new(stream, timeouts.read, deadline))
+    }
+
+    pub(crate) fn new(
+        stream: Box<dyn Transport>,
+        read_timeout: Option<Duration>,
+        deadline: Option<Instant>,
+    ) -> Self {
+        Self {
+            stream,
+            read_timeout,
+            deadline,
+        }
+    }
+
+    /**
+     * The underlying stream, e.g. to layer TLS over a tunnel opened through this connection.
+     */
+    pub(crate) fn into_inner(self) -> Box<dyn Transport> {
+        self.stream
+    }
+
+    /**
+     * Limit how long each read or write may block, and when the current exchange must be complete.
+     */
+    pub fn limit(&mut self, read_timeout: Option<Duration>, deadline: Option<Instant>) {
+        self.read_timeout = read_timeout;
+        self.deadline = deadline;
+    }
+
+    /**
+     * Set the stream's timeouts for the next read or write, given the time left before the deadline.
+     */
+    fn arm(&self) -> io::Result<()> {
+        let remaining = Timeout::remaining(self.deadline).map_err(Timeout::into_io)?;
+        let timeout = match (self.read_timeout, remaining) {
+            (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+            (timeout, remaining) => timeout.or(remaining),
+        };
+
+        self.stream.set_timeout(timeout)
+    }
+
+    /**
+     * Report stream timeouts as the limit that caused them.
+     */
+    fn disarm(&self, e: io::Error) -> io::Error {
+        match is_timeout(&e) {
+            true if Timeout::remaining(self.deadline).is_err() => Timeout::Total.into_io(),
+            true => Timeout::Read.into_io(),
+            false => e,
+        }
+    }
+}
+
+/**
+ * The TLS configuration used when none is given, trusting the Mozilla root certificates.
+ */
+pub fn default_tls_config() -> Arc<ClientConfig> {
+    let mut config = ClientConfig::new();
+    config
+        .root_store
+        .add_server_trust_anchors(&TLS_SERVER_ROOTS);
+
+    Arc::new(config)
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.arm()?;
+
+        let res = self.stream.read(buf);
+
+        res.map_err(|e| self.disarm(e))
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.arm()?;
+
+        let res = self.stream.write(buf);
+
+        res.map_err(|e| self.disarm(e))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.arm()?;
+
+        let res = self.stream.flush();
+
+        res.map_err(|e| self.disarm(e))
+    }
+}
@@ -0,0 +1,444 @@
+use {
+    crate::{
+        connection::Connection,
+        timeout::{is_timeout, Timeout, Timeouts},
+        Error, Protocol, Proxy, Request,
+    },
+    rustls::{ClientConfig, ClientSession, StreamOwned},
+    std::{
+        collections::VecDeque,
+        fmt::{self, Debug},
+        io::{self, Cursor, Read, Write},
+        net::{TcpStream, ToSocketAddrs},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    webpki::DNSNameRef,
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::PathBuf};
+
+/**
+ * A stream to a server that requests are written to and responses read from.
+ */
+pub trait Transport: Read + Write + Send {
+    /**
+     * Limit how long each read or write may block. Streams that cannot time out ignore this.
+     */
+    fn set_timeout(&self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for Box<dyn Transport> {
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_timeout(timeout)
+    }
+}
+
+impl Transport for TcpStream {
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)?;
+        self.set_write_timeout(timeout)
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixStream {
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.set_read_timeout(timeout)?;
+        self.set_write_timeout(timeout)
+    }
+}
+
+impl<T: Transport> Transport for StreamOwned<ClientSession, T> {
+    fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.set_timeout(timeout)
+    }
+}
+
+/**
+ * Opens the streams that requests are sent over, e.g. TCP connections or Unix domain sockets.
+ * Set one with *Request::connector* or *Client::connector* to replace the default of TLS over TCP.
+ */
+pub trait Connector: Debug + Send + Sync {
+    /**
+     * Open a stream to *host* on *port* for a request over *protocol*.
+     * Connecting should fail with Error::Timeout if it takes longer than *timeouts* or *deadline* allow.
+     */
+    fn connect(
+        &self,
+        protocol: Protocol,
+        host: &str,
+        port: u16,
+        timeouts: &Timeouts,
+        deadline: Option<Instant>,
+    ) -> Result<Box<dyn Transport>, Error>;
+}
+
+/**
+ * Connects over plain TCP, whatever the protocol.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpConnector;
+
+impl Connector for TcpConnector {
+    /**
+     * Try each address *host* resolves to in turn, until one connects.
+     */
+    fn connect(
+        &self,
+        _protocol: Protocol,
+        host: &str,
+        port: u16,
+        timeouts: &Timeouts,
+        deadline: Option<Instant>,
+    ) -> Result<Box<dyn Transport>, Error> {
+        let mut last_err = None;
+
+        for addr in (host, port).to_socket_addrs()? {
+            let remaining = Timeout::remaining(deadline).map_err(Error::Timeout)?;
+            let limit = match (timeouts.connect, remaining) {
+                (Some(timeout), Some(remaining)) if remaining < timeout => {
+                    Some((remaining, Timeout::Total))
+                }
+                (Some(timeout), _) => Some((timeout, Timeout::Connect)),
+                (None, Some(remaining)) => Some((remaining, Timeout::Total)),
+                (None, None) => None,
+            };
+            let res = match limit {
+                Some((limit, kind)) => {
+                    TcpStream::connect_timeout(&addr, limit).map_err(|e| timed_out(e, kind))
+                }
+                None => TcpStream::connect(addr).map_err(Error::from),
+            };
+
+            match res {
+                Ok(socket) => return Ok(Box::new(socket)),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No addresses for '{}'.", host),
+            )
+            .into()
+        }))
+    }
+}
+
+fn timed_out(e: io::Error, timeout: Timeout) -> Error {
+    match is_timeout(&e) {
+        true => Error::Timeout(timeout),
+        false => e.into(),
+    }
+}
+
+/**
+ * Negotiates TLS for HTTPS over the streams of another connector, by default plain TCP.
+ * Plain HTTP streams are passed through as they are.
+ */
+#[derive(Clone)]
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+    inner: Arc<dyn Connector>,
+}
+
+impl TlsConnector {
+    pub fn new(config: Arc<ClientConfig>) -> Self {
+        Self::with_inner(config, Arc::new(TcpConnector))
+    }
+
+    /**
+     * Layer TLS over the streams opened by *inner*.
+     */
+    pub fn with_inner(config: Arc<ClientConfig>, inner: Arc<dyn Connector>) -> Self {
+        Self { config, inner }
+    }
+}
+
+impl Debug for TlsConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConnector")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl Connector for TlsConnector {
+    fn connect(
+        &self,
+        protocol: Protocol,
+        host: &str,
+        port: u16,
+        timeouts: &Timeouts,
+        deadline: Option<Instant>,
+    ) -> Result<Box<dyn Transport>, Error> {
+        let stream = self
+            .inner
+            .connect(protocol, host, port, timeouts, deadline)?;
+
+        match protocol {
+            Protocol::HTTP => Ok(stream),
+            Protocol::HTTPS => {
+                let dns = DNSNameRef::try_from_ascii_str(host)
+                    .map_err(|_| Error::InvalidDnsName(host.to_string()))?;
+                let session = ClientSession::new(&self.config, dns);
+
+                Ok(Box::new(StreamOwned::new(session, stream)))
+            }
+        }
+    }
+}
+
+/**
+ * Connects to a proxy with another connector, and opens a connection to the server through it.
+ */
+#[derive(Debug)]
+pub(crate) struct ProxyConnector {
+    proxy: Proxy,
+    inner: Arc<dyn Connector>,
+}
+
+impl Connector for ProxyConnector {
+    fn connect(
+        &self,
+        protocol: Protocol,
+        host: &str,
+        port: u16,
+        timeouts: &Timeouts,
+        deadline: Option<Instant>,
+    ) -> Result<Box<dyn Transport>, Error> {
+        let stream = self.inner.connect(
+            Protocol::HTTP,
+            &self.proxy.host,
+            self.proxy.port,
+            timeouts,
+            deadline,
+        )?;
+        let mut conn = Connection::new(stream, timeouts.read, deadline);
+
+        self.proxy.open(&mut conn, protocol, host, port)?;
+        Ok(conn.into_inner())
+    }
+}
+
+/**
+ * The connector used when none is given: TLS over TCP, through *proxy* if there is one.
+ */
+pub(crate) fn default_connector(
+    proxy: Option<Proxy>,
+    tls_config: Arc<ClientConfig>,
+) -> Arc<dyn Connector> {
+    match proxy {
+        Some(proxy) => {
+            let inner = Arc::new(ProxyConnector {
+                proxy,
+                inner: Arc::new(TcpConnector),
+            });
+
+            Arc::new(TlsConnector::with_inner(tls_config, inner))
+        }
+        None => Arc::new(TlsConnector::new(tls_config)),
+    }
+}
+
+/**
+ * Connects to a Unix domain socket, such as the Docker daemon's, whatever the host and port.
+ */
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct UnixConnector {
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixConnector {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(unix)]
+impl Connector for UnixConnector {
+    fn connect(
+        &self,
+        _protocol: Protocol,
+        _host: &str,
+        _port: u16,
+        _timeouts: &Timeouts,
+        deadline: Option<Instant>,
+    ) -> Result<Box<dyn Transport>, Error> {
+        Timeout::remaining(deadline).map_err(Error::Timeout)?;
+
+        Ok(Box::new(UnixStream::connect(&self.path)?))
+    }
+}
+
+/**
+ * Serves canned responses from memory, recording the requests written to it, e.g. as a test double.
+ * Each connection reads the next queued response, and fails to connect once there are none left.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct MemoryConnector {
+    responses: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    requests: Arc<Mutex<Vec<Written>>>,
+}
+
+impl MemoryConnector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     * Queue a raw *response* for the next connection.
+     */
+    pub fn respond(&self, response: impl Into<Vec<u8>>) -> &Self {
+        self.responses.lock().unwrap().push_back(response.into());
+        self
+    }
+
+    /**
+     * The bytes written to each connection so far, in the order they were opened.
+     */
+    pub fn requests(&self) -> Vec<Vec<u8>> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|written| written.lock().unwrap().clone())
+            .collect()
+    }
+}
+
+impl Connector for MemoryConnector {
+    fn connect(
+        &self,
+        _protocol: Protocol,
+        _host: &str,
+        _port: u16,
+        _timeouts: &Timeouts,
+        _deadline: Option<Instant>,
+    ) -> Result<Box<dyn Transport>, Error> {
+        let response = self.responses.lock().unwrap().pop_front().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::ConnectionRefused, "No responses left.")
+        })?;
+        let written = Arc::new(Mutex::new(Vec::new()));
+
+        self.requests.lock().unwrap().push(written.clone());
+        Ok(Box::new(MemoryStream {
+            response: Cursor::new(response),
+            written,
+        }))
+    }
+}
+
+/**
+ * The bytes written to an in-memory connection, shared with its connector.
+ */
+type Written = Arc<Mutex<Vec<u8>>>;
+
+struct MemoryStream {
+    response: Cursor<Vec<u8>>,
+    written: Written,
+}
+
+impl Transport for MemoryStream {}
+
+impl Read for MemoryStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.response.read(buf)
+    }
+}
+
+impl Write for MemoryStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> Request<'a> {
+    /**
+     * Open connections with *connector* instead of the default of TLS over TCP.
+     * Proxy settings are then ignored, since the connector decides how to reach the server.
+     */
+    pub fn connector(&mut self, connector: Arc<dyn Connector>) -> &mut Self {
+        self.connector = Some(connector);
+        self
+    }
+}
+
+#[test]
+fn test_connectors() -> Result<(), Box<dyn std::error::Error>> {
+    better_panic::install();
+
+    let memory = MemoryConnector::new();
+    let mut req = Request::from_url("https://example.com/")?;
+
+    memory.respond("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    req.connector(Arc::new(memory.clone()));
+
+    assert_eq!(req.send()?.body.unwrap().as_bytes(), b"ok");
+    assert!(memory.requests()[0].starts_with(b"GET / HTTP/1.1\r\n"));
+    // Nothing is left to serve, so the next connection is refused.
+    assert!(
+        matches!(req.send(), Err(Error::Io(e)) if e.kind() == io::ErrorKind::ConnectionRefused)
+    );
+
+    // A client uses the connector of each request, and only reuses connections it opened.
+    let client = crate::Client::new();
+
+    for body in &["first", "second"] {
+        let memory = MemoryConnector::new();
+        let mut req = Request::from_url("https://example.com/")?;
+
+        memory.respond(format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        ));
+        req.connector(Arc::new(memory.clone())).keep_alive();
+
+        assert_eq!(client.send(&req)?.body.unwrap().as_bytes(), body.as_bytes());
+        assert_eq!(memory.requests().len(), 1);
+    }
+    assert_eq!(client.idle_connections(), 2);
+
+    #[cfg(unix)]
+    {
+        use std::{os::unix::net::UnixListener, thread};
+
+        let path = std::env::temp_dir().join(format!("http-rs-{}.sock", crate::random_hex()));
+        let listener = UnixListener::bind(&path)?;
+        let server = thread::spawn(move || -> io::Result<Vec<u8>> {
+            let (mut socket, _) = listener.accept()?;
+            let mut buf = [0; 512];
+            let len = socket.read(&mut buf)?;
+
+            socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\ndocker")?;
+            Ok(buf[..len].to_vec())
+        });
+        let mut req = Request::from_url("http://localhost/v1.41/version")?;
+
+        req.connector(Arc::new(UnixConnector::new(&path)));
+
+        let res = req.send();
+
+        std::fs::remove_file(&path)?;
+        assert_eq!(res?.body.unwrap().as_bytes(), b"docker");
+        assert!(server
+            .join()
+            .unwrap()?
+            .starts_with(b"GET /v1.41/version HTTP/1.1\r\n"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,539 @@
+use {
+    crate::{suffix::PublicSuffixList, CommonHeaders::*, Error, Headers, Protocol, Url},
+    std::{
+        fs,
+        net::IpAddr,
+        path::Path,
+        sync::Mutex,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/**
+ * Whether a cookie is sent with cross-site requests. It is stored but not enforced,
+ * since a client has no notion of the site that started a request.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/**
+ * A cookie set by a server, as stored in a CookieJar.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    // The host the cookie is sent to, along with its subdomains unless *host_only* is set.
+    pub domain: String,
+    pub host_only: bool,
+    // The cookie is sent to this path and the paths below it.
+    pub path: String,
+    // When the cookie expires. Session cookies have none, and last as long as the jar does.
+    pub expires: Option<SystemTime>,
+    // Only send the cookie over HTTPS.
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /**
+     * Parse a *SET-COOKIE* value received from *url*, as in RFC 6265 section 5.2.
+     * Returns None for malformed cookies, and for cookies that *url* is not allowed to set.
+     */
+    pub fn parse(set_cookie: &str, url: &Url, now: SystemTime) -> Option<Self> {
+        let host = url.host.to_ascii_lowercase();
+        let mut attributes = set_cookie.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let mut cookie = Self {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url.path),
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        };
+        let mut max_age = None;
+
+        if cookie.name.is_empty() {
+            return None;
+        }
+
+        for attribute in attributes {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+
+            match key.trim().to_ascii_lowercase().as_str() {
+                "expires" => cookie.expires = parse_cookie_date(value).or(cookie.expires),
+                "max-age" => max_age = value.parse::<i64>().ok().or(max_age),
+                "domain" => match value.trim_start_matches('.').to_ascii_lowercase() {
+                    domain if domain.is_empty() => {}
+                    domain => {
+                        cookie.domain = domain;
+                        cookie.host_only = false;
+                    }
+                },
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "path" => cookie.path = default_path(url.path),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => {
+                    cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => Some(SameSite::Strict),
+                        "lax" => Some(SameSite::Lax),
+                        "none" => Some(SameSite::None),
+                        _ => None,
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Max-Age takes precedence over Expires, and a non-positive one expires the cookie at once.
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(match max_age {
+                max_age if max_age <= 0 => UNIX_EPOCH,
+                max_age => now
+                    .checked_add(Duration::from_secs(max_age as u64))
+                    .unwrap_or_else(latest_expiry),
+            });
+        }
+
+        let insecure = url.protocol == Protocol::HTTP && cookie.secure;
+
+        if insecure || (!cookie.host_only && !domain_match(&host, &cookie.domain)) {
+            return None;
+        }
+
+        Some(cookie)
+    }
+
+    /**
+     * Whether the cookie should be sent with a request to *url*.
+     */
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = url.host.to_ascii_lowercase();
+        let domain = match self.host_only {
+            true => host == self.domain,
+            false => domain_match(&host, &self.domain),
+        };
+
+        domain
+            && path_match(url.path, &self.path)
+            && (!self.secure || url.protocol == Protocol::HTTPS)
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+/**
+ * Stores the cookies set by servers, and chooses which to send with each request.
+ * Cookies are kept per domain and path, and a cookie with the same name, domain and path replaces
+ * the one before it. Attach it to a Client with *Client::cookie_jar*.
+ */
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+    suffixes: PublicSuffixList,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     * Create a jar that rejects cookies set for the public suffixes in *suffixes*.
+     */
+    pub fn with_public_suffixes(suffixes: PublicSuffixList) -> Self {
+        Self {
+            cookies: Mutex::new(Vec::new()),
+            suffixes,
+        }
+    }
+
+    /**
+     * Store the cookies set by the *SET-COOKIE* headers of a response from *url*.
+     * Cookies set for a public suffix are only kept for the host that set them, if it is that suffix.
+     */
+    pub fn store(&self, url: &Url, headers: &Headers) {
+        let now = SystemTime::now();
+
+        for value in headers.get_all(&SetCookie.to_string()) {
+            let mut cookie = match Cookie::parse(value, url, now) {
+                Some(cookie) => cookie,
+                None => continue,
+            };
+
+            if !cookie.host_only && self.suffixes.is_public(&cookie.domain) {
+                match cookie.domain.eq_ignore_ascii_case(url.host) {
+                    true => cookie.host_only = true,
+                    false => continue,
+                }
+            }
+
+            self.insert(cookie);
+        }
+    }
+
+    /**
+     * Store *cookie*, replacing any with the same name, domain and path. Expired cookies only remove.
+     */
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.lock();
+        let existing = cookies.iter().position(|c| {
+            c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+        });
+
+        match (existing, cookie.is_expired(SystemTime::now())) {
+            (Some(i), true) => {
+                cookies.remove(i);
+            }
+            (Some(i), false) => cookies[i] = cookie,
+            (None, true) => {}
+            (None, false) => cookies.push(cookie),
+        }
+    }
+
+    /**
+     * The cookies currently stored, without those that have expired.
+     */
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let now = SystemTime::now();
+
+        self.lock()
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now))
+            .cloned()
+            .collect()
+    }
+
+    /**
+     * The *COOKIE* value to send with a request to *url*, if any cookies match it.
+     * Cookies with longer paths come first, and otherwise those stored first.
+     */
+    pub fn header_value(&self, url: &Url) -> Option<String> {
+        let mut cookies = self.cookies();
+
+        cookies.retain(|cookie| cookie.matches(url));
+        cookies.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+
+        match cookies.is_empty() {
+            true => None,
+            false => Some(
+                cookies
+                    .iter()
+                    .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+        }
+    }
+
+    /**
+     * Load cookies from a Netscape cookie file, as written by *save*, curl or wget.
+     * Lines that cannot be parsed are skipped.
+     */
+    pub fn load(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let contents = fs::read_to_string(path)?;
+
+        for line in contents.lines() {
+            let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => (line, true),
+                None if line.starts_with('#') => continue,
+                None => (line, false),
+            };
+            let fields: Vec<&str> = line.split('\t').collect();
+
+            if let [domain, subdomains, path, secure, expires, name, value] = fields[..] {
+                let expires = match expires.parse::<u64>() {
+                    Ok(0) => None,
+                    Ok(secs) => Some(
+                        UNIX_EPOCH
+                            .checked_add(Duration::from_secs(secs))
+                            .unwrap_or_else(latest_expiry),
+                    ),
+                    Err(_) => continue,
+                };
+
+                self.insert(Cookie {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                    domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+                    host_only: !subdomains.eq_ignore_ascii_case("TRUE"),
+                    path: path.to_string(),
+                    expires,
+                    secure: secure.eq_ignore_ascii_case("TRUE"),
+                    http_only,
+                    same_site: None,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Save the cookies to a Netscape cookie file. Session cookies are saved with an expiry of 0.
+     */
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut contents = String::from("# Netscape HTTP Cookie File\n");
+        let flag = |set: bool| if set { "TRUE" } else { "FALSE" };
+
+        for cookie in self.cookies() {
+            let expires = cookie
+                .expires
+                .and_then(|expires| expires.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |expires| expires.as_secs());
+
+            contents.push_str(&format!(
+                "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if cookie.http_only { "#HttpOnly_" } else { "" },
+                if cookie.host_only { "" } else { "." },
+                cookie.domain,
+                flag(!cookie.host_only),
+                cookie.path,
+                flag(cookie.secure),
+                expires,
+                cookie.name,
+                cookie.value
+            ));
+        }
+
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Cookie>> {
+        self.cookies.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/**
+ * Whether *host* is *domain* or a subdomain of it, as in RFC 6265 section 5.1.3.
+ */
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.')
+            && host.parse::<IpAddr>().is_err())
+}
+
+/**
+ * Whether *path* is *cookie_path* or below it, as in RFC 6265 section 5.1.4.
+ */
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    let path = if path.is_empty() { "/" } else { path };
+
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+/**
+ * The path a cookie applies to when it does not set one: the directory of the request path.
+ */
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(end) if end > 0 && path.starts_with('/') => path[..end].to_string(),
+        _ => "/".to_string(),
+    }
+}
+
+/**
+ * The expiry given to cookies whose lifetime goes beyond what *SystemTime* can hold:
+ * the last second of the year 9999, in place of the last representable date of RFC 6265 section 5.2.2.
+ */
+fn latest_expiry() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(253_402_300_799)
+}
+
+/**
+ * Parse a cookie date with the lenient algorithm of RFC 6265 section 5.1.1,
+ * which accepts the many date formats servers send.
+ */
+fn parse_cookie_date(s: &str) -> Option<SystemTime> {
+    let is_delimiter = |c: char| matches!(c, '\x09' | '\x20'..='\x2f' | '\x3b'..='\x40' | '\x5b'..='\x60' | '\x7b'..='\x7e');
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+
+    for token in s.split(is_delimiter).filter(|token| !token.is_empty()) {
+        if time.is_none() {
+            if let Some(parsed) = parse_time(token) {
+                time = Some(parsed);
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some(parsed) = leading_digits(token, 1, 2) {
+                day = Some(parsed);
+                continue;
+            }
+        }
+        if month.is_none() {
+            let name = token.get(..3).map(str::to_ascii_lowercase);
+
+            if let Some(i) = MONTHS
+                .iter()
+                .position(|month| name.as_deref() == Some(month))
+            {
+                month = Some(i as u64 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            year = leading_digits(token, 2, 4);
+        }
+    }
+
+    let ((hour, minute, second), day, month, year) = (time?, day?, month?, year?);
+    let year = match year {
+        70..=99 => year + 1900,
+        0..=69 => year + 2000,
+        year => year,
+    };
+
+    if !(1..=31).contains(&day) || year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let secs = days_from_civil(year as i64, month, day) * 86400
+        + (hour * 3600 + minute * 60 + second) as i64;
+
+    match secs {
+        secs if secs >= 0 => UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64)),
+        secs => UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs())),
+    }
+}
+
+/**
+ * Parse a time of the form *hh:mm:ss*, where each field has one or two digits.
+ */
+fn parse_time(token: &str) -> Option<(u64, u64, u64)> {
+    let mut fields = token.splitn(3, ':');
+    let hour = fields.next().filter(|f| (1..=2).contains(&f.len()))?;
+    let minute = fields.next().filter(|f| (1..=2).contains(&f.len()))?;
+    let second = leading_digits(fields.next()?, 1, 2)?;
+
+    Some((hour.parse().ok()?, minute.parse().ok()?, second))
+}
+
+/**
+ * Parse the *min* to *max* digits that start *token*, if they are not followed by another digit.
+ */
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<u64> {
+    let len = token.bytes().take_while(u8::is_ascii_digit).count();
+
+    match (min..=max).contains(&len) {
+        true => token[..len].parse().ok(),
+        false => None,
+    }
+}
+
+/**
+ * The number of days from 1970-01-01 to a date in the proleptic Gregorian calendar.
+ */
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[test]
+fn test_cookie_jar() -> Result<(), Error> {
+    better_panic::install();
+
+    let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+    assert_eq!(
+        parse_cookie_date("Wed, 21 Oct 2015 07:28:00 GMT"),
+        Some(at(1445412480))
+    );
+    assert_eq!(
+        parse_cookie_date("Wednesday, 21-Oct-15 07:28:00 GMT"),
+        Some(at(1445412480))
+    );
+    assert_eq!(
+        parse_cookie_date("Wed Oct 21 07:28:00 2015"),
+        Some(at(1445412480))
+    );
+    assert_eq!(parse_cookie_date("21 Oct 2015"), None);
+
+    let url = Url::parse("https://www.example.com/account/login")?;
+    let now = SystemTime::now();
+    let cookie = Cookie::parse(
+        "sid=abc; Domain=.Example.com; Path=/; Max-Age=3600; Secure; HttpOnly; SameSite=Lax",
+        &url,
+        now,
+    )
+    .unwrap();
+
+    assert_eq!(cookie.domain, "example.com");
+    assert!(!cookie.host_only);
+    assert_eq!(cookie.expires, Some(now + Duration::from_secs(3600)));
+    assert!(cookie.secure && cookie.http_only);
+    assert_eq!(cookie.same_site, Some(SameSite::Lax));
+    assert_eq!(Cookie::parse("a=1", &url, now).unwrap().path, "/account");
+    assert!(Cookie::parse("a=1; Domain=other.com", &url, now).is_none());
+    assert!(Cookie::parse("no-value", &url, now).is_none());
+    assert_eq!(
+        Cookie::parse("a=1; Max-Age=9223372036854775807", &url, now)
+            .unwrap()
+            .expires,
+        Some(latest_expiry())
+    );
+
+    let jar = CookieJar::new();
+    let mut headers = Headers::new();
+
+    headers.append("Set-Cookie", "sid=abc; Domain=example.com; Path=/");
+    headers.append("Set-Cookie", "pref=dark; Path=/account");
+    headers.append("Set-Cookie", "tracker=1; Domain=com");
+    headers.append("Set-Cookie", "old=1; Expires=Thu, 01 Jan 1970 00:00:00 GMT");
+    jar.store(&url, &headers);
+
+    assert_eq!(jar.cookies().len(), 2);
+    assert_eq!(
+        jar.header_value(&Url::parse("https://www.example.com/account/settings")?),
+        Some("pref=dark; sid=abc".to_string())
+    );
+    assert_eq!(
+        jar.header_value(&Url::parse("http://api.example.com/")?),
+        Some("sid=abc".to_string())
+    );
+    assert_eq!(jar.header_value(&Url::parse("https://example.org/")?), None);
+
+    let mut headers = Headers::new();
+
+    headers.append("Set-Cookie", "sid=; Domain=example.com; Path=/; Max-Age=0");
+    jar.store(&url, &headers);
+    assert_eq!(jar.cookies().len(), 1);
+
+    let path = std::env::temp_dir().join(format!("http-rs-cookies-{}.txt", std::process::id()));
+    let loaded = CookieJar::new();
+
+    jar.save(&path)?;
+    loaded.load(&path)?;
+    fs::remove_file(&path)?;
+    assert_eq!(loaded.cookies(), jar.cookies());
+
+    Ok(())
+}
@@ -0,0 +1,366 @@
+#[cfg(feature = "zstd")]
+use std::io::BufReader;
+use {
+    crate::{CommonHeaders::*, Error, Headers},
+    flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder},
+    std::{
+        fmt::{self, Display},
+        io::{self, Read},
+    },
+};
+
+/**
+ * Limits on decoding a compressed body, to protect against decompression bombs.
+ * Unset limits are not enforced. By default the size is unlimited and the ratio is limited to 200:1
+ * once 1 MiB has been decoded, so a body can decode to at most 1 MiB or 200 times its size, whichever is larger.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecompressionLimits {
+    // The most bytes a body may decode to.
+    pub max_size: Option<u64>,
+    // The most bytes a body may decode to for each encoded byte. A body at exactly this ratio is accepted.
+    pub max_ratio: Option<u64>,
+    // The ratio limit only applies once this many bytes have been decoded,
+    // so that small but highly compressible bodies are not rejected.
+    pub ratio_threshold: u64,
+}
+
+impl Default for DecompressionLimits {
+    fn default() -> Self {
+        Self {
+            max_size: None,
+            max_ratio: Some(200),
+            ratio_threshold: 1 << 20,
+        }
+    }
+}
+
+impl DecompressionLimits {
+    /**
+     * Check that *decoded* bytes from *encoded* bytes of input are within the limits.
+     */
+    pub(crate) fn check(&self, encoded: u64, decoded: u64) -> Result<(), Error> {
+        if let Some(max) = self.max_size.filter(|&max| decoded > max) {
+            return Err(Error::DecompressionLimit(DecompressionLimit::Size(max)));
+        }
+
+        match self.max_ratio {
+            Some(max)
+                if decoded > self.ratio_threshold
+                    && decoded > encoded.max(1).saturating_mul(max) =>
+            {
+                Err(Error::DecompressionLimit(DecompressionLimit::Ratio(max)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/**
+ * Which limit was exceeded while decoding a body, along with its value.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecompressionLimit {
+    Size(u64),
+    Ratio(u64),
+}
+
+impl Display for DecompressionLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecompressionLimit::Size(max) => write!(f, "size limit of {} bytes", max),
+            DecompressionLimit::Ratio(max) => write!(f, "compression ratio limit of {}:1", max),
+        }
+    }
+}
+
+/**
+ * The content-codings that can be decoded, as advertised in *ACCEPT-ENCODING*.
+ */
+pub(crate) fn accept_encoding() -> String {
+    let encodings: &[&str] = &[
+        "gzip",
+        "deflate",
+        #[cfg(feature = "brotli")]
+        "br",
+        #[cfg(feature = "zstd")]
+        "zstd",
+    ];
+
+    encodings.join(", ")
+}
+
+/**
+ * A reader with the bytes already read from it to sniff its format put back in front.
+ */
+type Sniffed<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+/**
+ * Decodes the content-codings of a body as it is read, undoing them in the reverse order they were applied.
+ */
+pub(crate) enum Decoder<R: Read> {
+    Identity(R),
+    Gzip(Box<GzDecoder<Decoder<R>>>),
+    // The *deflate* content-coding is the zlib format.
+    Deflate(Box<ZlibDecoder<Sniffed<Decoder<R>>>>),
+    // Raw deflate, which some servers send for the *deflate* content-coding instead.
+    RawDeflate(Box<DeflateDecoder<Sniffed<Decoder<R>>>>),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<brotli::Decompressor<Decoder<R>>>),
+    #[cfg(feature = "zstd")]
+    Zstd(Box<zstd::Decoder<'static, BufReader<Decoder<R>>>>),
+}
+
+impl<R: Read> Decoder<R> {
+    /**
+     * Decode *reader* according to the *CONTENT-ENCODING* of *headers*.
+     */
+    pub(crate) fn new(reader: R, headers: &Headers) -> Result<Self, Error> {
+        let mut decoder = Decoder::Identity(reader);
+
+        for encoding in Self::encodings(headers).iter().rev() {
+            decoder = match encoding.as_str() {
+                "gzip" | "x-gzip" => Decoder::Gzip(Box::new(GzDecoder::new(decoder))),
+                "deflate" | "zlib" => Self::deflate(decoder)?,
+                #[cfg(feature = "brotli")]
+                "br" => Decoder::Brotli(Box::new(brotli::Decompressor::new(decoder, 4096))),
+                #[cfg(feature = "zstd")]
+                "zstd" => Decoder::Zstd(Box::new(zstd::Decoder::new(decoder)?)),
+                "identity" => decoder,
+                encoding => return Err(Error::UnsupportedEncoding(encoding.to_string())),
+            };
+        }
+
+        Ok(decoder)
+    }
+
+    /**
+     * Decode the *deflate* content-coding, as zlib if *reader* starts with a valid zlib header
+     * (RFC 1950 section 2.2), and as raw deflate otherwise.
+     */
+    fn deflate(mut reader: Decoder<R>) -> Result<Self, Error> {
+        let mut header = Vec::with_capacity(2);
+
+        (&mut reader).take(2).read_to_end(&mut header)?;
+
+        let zlib = match header[..] {
+            [cmf, flg] => cmf & 0x0f == 8 && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0,
+            _ => false,
+        };
+        let reader = io::Cursor::new(header).chain(reader);
+
+        Ok(match zlib {
+            true => Decoder::Deflate(Box::new(ZlibDecoder::new(reader))),
+            false => Decoder::RawDeflate(Box::new(DeflateDecoder::new(reader))),
+        })
+    }
+
+    /**
+     * The content-codings listed in *headers*, lowercased, in the order they were applied.
+     */
+    pub(crate) fn encodings(headers: &Headers) -> Vec<String> {
+        headers
+            .get_all(&ContentEncoding.to_string())
+            .flat_map(|value| value.split(','))
+            .map(|encoding| encoding.trim().to_ascii_lowercase())
+            .filter(|encoding| !encoding.is_empty())
+            .collect()
+    }
+
+    /**
+     * The undecoded reader.
+     */
+    pub(crate) fn get_ref(&self) -> &R {
+        match self {
+            Decoder::Identity(reader) => reader,
+            Decoder::Gzip(decoder) => decoder.get_ref().get_ref(),
+            Decoder::Deflate(decoder) => decoder.get_ref().get_ref().1.get_ref(),
+            Decoder::RawDeflate(decoder) => decoder.get_ref().get_ref().1.get_ref(),
+            #[cfg(feature = "brotli")]
+            Decoder::Brotli(decoder) => decoder.get_ref().get_ref(),
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(decoder) => decoder.get_ref().get_ref().get_ref(),
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        match self {
+            Decoder::Identity(reader) => reader,
+            Decoder::Gzip(decoder) => decoder.get_mut().get_mut(),
+            Decoder::Deflate(decoder) => decoder.get_mut().get_mut().1.get_mut(),
+            Decoder::RawDeflate(decoder) => decoder.get_mut().get_mut().1.get_mut(),
+            #[cfg(feature = "brotli")]
+            Decoder::Brotli(decoder) => decoder.get_mut().get_mut(),
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(decoder) => decoder.get_mut().get_mut().get_mut(),
+        }
+    }
+
+    pub(crate) fn is_identity(&self) -> bool {
+        matches!(self, Decoder::Identity(_))
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decoder::Identity(reader) => reader.read(buf),
+            Decoder::Gzip(decoder) => decoder.read(buf),
+            Decoder::Deflate(decoder) => decoder.read(buf),
+            Decoder::RawDeflate(decoder) => decoder.read(buf),
+            #[cfg(feature = "brotli")]
+            Decoder::Brotli(decoder) => decoder.read(buf),
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+#[test]
+fn test_decode_stacked_encodings() -> Result<(), Error> {
+    use {
+        flate2::{
+            write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+            Compression,
+        },
+        std::io::Write,
+    };
+
+    better_panic::install();
+
+    let data = b"stacked content-codings";
+
+    let mut deflated = ZlibEncoder::new(Vec::new(), Compression::default());
+    deflated.write_all(data)?;
+    let mut gzipped = GzEncoder::new(Vec::new(), Compression::default());
+    gzipped.write_all(&deflated.finish()?)?;
+    let encoded = gzipped.finish()?;
+
+    let mut headers = Headers::new();
+    let mut decoded = Vec::new();
+
+    headers.insert("Content-Encoding", "deflate, gzip");
+    Decoder::new(&encoded[..], &headers)?.read_to_end(&mut decoded)?;
+    assert_eq!(decoded, data);
+
+    let mut raw = DeflateEncoder::new(Vec::new(), Compression::default());
+    let mut decoded = Vec::new();
+
+    raw.write_all(data)?;
+    headers.insert("Content-Encoding", "deflate");
+    Decoder::new(&raw.finish()?[..], &headers)?.read_to_end(&mut decoded)?;
+    assert_eq!(decoded, data);
+
+    // Empty bodies are often labelled with a content-coding, and are passed through as they are.
+    let mut body = crate::SinglePartBody { data: Vec::new() };
+    assert!(body.decompress(&headers)?.data.is_empty());
+
+    let memory = crate::MemoryConnector::new();
+    let mut req = crate::Request::from_url("http://example.com/")?;
+
+    memory
+        .respond("HTTP/1.1 201 Created\r\nContent-Encoding: gzip\r\nContent-Length: 0\r\n\r\n")
+        .respond("HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\nConnection: close\r\n\r\n");
+    #[cfg(feature = "chunks")]
+    memory.respond(
+        "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+    );
+    req.connector(std::sync::Arc::new(memory.clone()));
+
+    for _ in 0..if cfg!(feature = "chunks") { 3 } else { 2 } {
+        assert!(req.send()?.body.unwrap().as_bytes().is_empty());
+    }
+
+    headers.insert("Content-Encoding", "compress");
+    assert!(matches!(
+        Decoder::new(&encoded[..], &headers),
+        Err(Error::UnsupportedEncoding(_))
+    ));
+
+    #[cfg(all(feature = "brotli", feature = "zstd"))]
+    {
+        let mut encoded = Vec::new();
+        let mut decoded = Vec::new();
+        let zstd = zstd::encode_all(&data[..], 0)?;
+
+        brotli::BrotliCompress(&mut &zstd[..], &mut encoded, &Default::default())?;
+        headers.insert("Content-Encoding", "zstd, br");
+        Decoder::new(&encoded[..], &headers)?.read_to_end(&mut decoded)?;
+        assert_eq!(decoded, data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_decompression_limits() -> Result<(), Error> {
+    use {
+        crate::{Method, SinglePartBody, StreamingResponse},
+        flate2::{write::GzEncoder, Compression},
+        std::io::Write,
+    };
+
+    better_panic::install();
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&vec![0; 10 << 20])?;
+    let gzipped = encoder.finish()?;
+
+    let mut headers = Headers::new();
+    headers.insert("Content-Encoding", "gzip");
+
+    let mut body = SinglePartBody {
+        data: gzipped.clone(),
+    };
+    assert!(matches!(
+        body.decompress(&headers),
+        Err(Error::DecompressionLimit(DecompressionLimit::Ratio(200)))
+    ));
+
+    // A body at exactly the limit is accepted, one byte more is not.
+    let limits = DecompressionLimits::default();
+    assert!(limits.check(1 << 20, 200 << 20).is_ok());
+    assert!(limits.check(1 << 20, (200 << 20) + 1).is_err());
+
+    let strict = DecompressionLimits {
+        ratio_threshold: 0,
+        ..limits
+    };
+    assert!(limits.check(1, 1000).is_ok());
+    assert!(matches!(
+        strict.check(1, 1000),
+        Err(Error::DecompressionLimit(DecompressionLimit::Ratio(200)))
+    ));
+
+    let limits = DecompressionLimits {
+        max_size: Some(1 << 20),
+        max_ratio: None,
+        ..Default::default()
+    };
+    assert!(matches!(
+        body.decompress_with(&headers, &limits),
+        Err(Error::DecompressionLimit(DecompressionLimit::Size(_)))
+    ));
+
+    let unlimited = DecompressionLimits {
+        max_size: None,
+        max_ratio: None,
+        ..Default::default()
+    };
+    body.decompress_with(&headers, &unlimited)?;
+    assert_eq!(body.data.len(), 10 << 20);
+
+    let mut stream = format!(
+        "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+        gzipped.len()
+    )
+    .into_bytes();
+    stream.extend_from_slice(&gzipped);
+
+    assert!(matches!(
+        StreamingResponse::read_from(&stream[..], &Method::GET)?.collect(),
+        Err(Error::DecompressionLimit(DecompressionLimit::Ratio(200)))
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use {
+    flate2::{
+        read::{GzEncoder, ZlibEncoder},
+        Compression,
+    },
+    std::io::{self, Read},
+};
+
+/**
+ * A content-coding that a request body can be compressed with.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    // The zlib format, which is what the *deflate* content-coding means.
+    Deflate,
+    #[cfg(feature = "brotli")]
+    Brotli,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Encoding {
+    /**
+     * The name of the content-coding, as sent in *CONTENT-ENCODING*.
+     */
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => "br",
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    /**
+     * Compress the data of *reader* as it is read.
+     */
+    pub(crate) fn encode<R: Read + Send + 'static>(
+        &self,
+        reader: R,
+    ) -> io::Result<Box<dyn Read + Send>> {
+        Ok(match self {
+            Encoding::Gzip => Box::new(GzEncoder::new(reader, Compression::default())),
+            Encoding::Deflate => Box::new(ZlibEncoder::new(reader, Compression::default())),
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => Box::new(brotli::CompressorReader::new(reader, 4096, 9, 22)),
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => Box::new(zstd::stream::read::Encoder::new(reader, 0)?),
+        })
+    }
+}
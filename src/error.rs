@@ -0,0 +1,201 @@
+use {
+    crate::{
+        parse::{self, ErrorKind, Input},
+        timeout::Timeout,
+        DecompressionLimit,
+    },
+    cookie_factory::GenError,
+    rustls::TLSError,
+    std::{
+        error::Error as StdError,
+        fmt::{self, Display},
+        io,
+    },
+};
+
+/**
+ * Errors that can occur while building, sending or parsing HTTP messages.
+ */
+#[derive(Debug)]
+pub enum Error {
+    // An I/O error on the underlying socket.
+    Io(io::Error),
+    // The TLS session could not be established or was corrupted.
+    Tls(TLSError),
+    // The host is not a valid DNS name, so a TLS session cannot be made for it.
+    InvalidDnsName(String),
+    // The message could not be parsed. Contains the context chain, innermost first.
+    Parse(Vec<ErrorKind>),
+    // The input ended before a complete message could be parsed.
+    Incomplete,
+    // Connecting, reading or the whole exchange took longer than its limit.
+    Timeout(Timeout),
+    // The status line, header section or trailers were longer than this many bytes.
+    HeadTooLarge(usize),
+    // The server closed the connection without sending a response.
+    ConnectionClosed,
+    // The *CONTENT-LENGTH* header is not a valid length, or has conflicting values.
+    InvalidContentLength(String),
+    // The request's streamed body was already sent, and cannot be read again.
+    BodyConsumed,
+    // The request could not be serialized.
+    Serialize(GenError),
+    // The request was sent without a host.
+    MissingHost,
+    // The method name is not a valid token.
+    InvalidMethod(String),
+    // The URL could not be parsed.
+    InvalidUrl(String),
+    // The URL's scheme is not *http* or *https*.
+    UnsupportedScheme(String),
+    // More redirects were received than the redirect policy allows.
+    TooManyRedirects(usize),
+    // The body could not be decompressed.
+    Decompression(io::Error),
+    // The decoded body exceeded a limit, and may be a decompression bomb.
+    DecompressionLimit(DecompressionLimit),
+    // The body was compressed with a content-coding or transfer-coding that is not supported.
+    UnsupportedEncoding(String),
+    // The proxy refused to open a tunnel, responding with this status code.
+    ProxyTunnel(u16),
+    // The SOCKS5 handshake with a proxy failed.
+    Socks(String),
+    // A body could not be converted to or from JSON.
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Tls(e) => write!(f, "TLS error: {}", e),
+            Error::InvalidDnsName(host) => write!(f, "Invalid DNS name '{}'.", host),
+            Error::Parse(errors) => {
+                write!(f, "Parsing error")?;
+                for kind in errors.iter().rev() {
+                    match kind {
+                        ErrorKind::Context(ctx) => write!(f, " ...in {}", ctx)?,
+                        ErrorKind::Nom(err) => write!(f, ": nom error {:?}", err)?,
+                        ErrorKind::Custom(err) => write!(f, ": {}", err)?,
+                        ErrorKind::Malformed => write!(f, ": malformed packet")?,
+                    }
+                }
+                Ok(())
+            }
+            Error::Incomplete => write!(f, "Incomplete message."),
+            Error::Timeout(timeout) => write!(f, "Request exceeded its {}.", timeout),
+            Error::HeadTooLarge(max) => {
+                write!(f, "Message head exceeded the limit of {} bytes.", max)
+            }
+            Error::ConnectionClosed => {
+                write!(f, "Connection closed before a response was received.")
+            }
+            Error::InvalidContentLength(len) => write!(f, "Invalid content length '{}'.", len),
+            Error::BodyConsumed => write!(f, "The request body was already sent."),
+            Error::Serialize(e) => write!(f, "Serialization error: {}", e),
+            Error::MissingHost => write!(f, "No host provided."),
+            Error::InvalidMethod(method) => write!(f, "Invalid HTTP method '{}'.", method),
+            Error::InvalidUrl(url) => write!(f, "Invalid URL '{}'.", url),
+            Error::UnsupportedScheme(scheme) => write!(f, "Unsupported URL scheme '{}'.", scheme),
+            Error::TooManyRedirects(max) => write!(f, "Exceeded the limit of {} redirects.", max),
+            Error::Decompression(e) => write!(f, "Decompression error: {}", e),
+            Error::DecompressionLimit(limit) => {
+                write!(f, "Decompressed body exceeded its {}.", limit)
+            }
+            Error::UnsupportedEncoding(encoding) => {
+                write!(f, "Unsupported coding '{}'.", encoding)
+            }
+            Error::ProxyTunnel(status) => {
+                write!(
+                    f,
+                    "The proxy refused to open a tunnel, with status {}.",
+                    status
+                )
+            }
+            Error::Socks(message) => write!(f, "SOCKS error: {}", message),
+            #[cfg(feature = "json")]
+            Error::Json(e) => write!(f, "JSON error: {}", e),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io(e) | Error::Decompression(e) => Some(e),
+            Error::Tls(e) => Some(e),
+            Error::Serialize(e) => Some(e),
+            #[cfg(feature = "json")]
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /**
+     * rustls reports TLS failures through io::Error, and connections report timeouts the same way,
+     * so unwrap those into Error::Tls and Error::Timeout.
+     * Errors that were wrapped to pass through a Read implementation are unwrapped as they were.
+     */
+    fn from(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            let inner = e.into_inner().and_then(|inner| inner.downcast().ok());
+            return *inner.expect("the inner error is an Error");
+        }
+
+        let inner = e.get_ref();
+
+        if let Some(tls) = inner.and_then(|inner| inner.downcast_ref::<TLSError>()) {
+            return Error::Tls(tls.clone());
+        }
+        if let Some(timeout) = inner.and_then(|inner| inner.downcast_ref::<Timeout>()) {
+            return Error::Timeout(*timeout);
+        }
+
+        Error::Io(e)
+    }
+}
+
+impl From<Error> for io::Error {
+    /**
+     * Wrap an error to return it from a Read implementation, keeping it intact for From<io::Error>.
+     */
+    fn from(e: Error) -> Self {
+        let kind = match &e {
+            Error::Io(e) => e.kind(),
+            Error::Timeout(_) => io::ErrorKind::TimedOut,
+            Error::Incomplete | Error::ConnectionClosed => io::ErrorKind::UnexpectedEof,
+            _ => io::ErrorKind::InvalidData,
+        };
+
+        io::Error::new(kind, e)
+    }
+}
+
+impl From<TLSError> for Error {
+    fn from(e: TLSError) -> Self {
+        Error::Tls(e)
+    }
+}
+
+impl From<GenError> for Error {
+    fn from(e: GenError) -> Self {
+        match e {
+            GenError::IoError(e) => e.into(),
+            e => Error::Serialize(e),
+        }
+    }
+}
+
+impl<'a> From<nom::Err<parse::Error<Input<'a>>>> for Error {
+    fn from(e: nom::Err<parse::Error<Input<'a>>>) -> Self {
+        match e {
+            nom::Err::Incomplete(_) => Error::Incomplete,
+            nom::Err::Error(e) | nom::Err::Failure(e) => {
+                Error::Parse(e.errors.into_iter().map(|(_, kind)| kind).collect())
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use {
+    crate::url::{percent_decode, percent_encode_except},
+    std::borrow::Borrow,
+};
+
+/**
+ * The media type of a form body.
+ */
+pub(crate) const FORM_URLENCODED: &str = "application/x-www-form-urlencoded";
+
+/**
+ * Encode *fields* as an *application/x-www-form-urlencoded* string, in the order given.
+ * Spaces become *+*, and everything but alphanumerics and *\*-._* is percent-encoded.
+ */
+pub fn form_encode<K, V>(fields: impl IntoIterator<Item = (K, V)>) -> String
+where
+    K: Borrow<str>,
+    V: Borrow<str>,
+{
+    fields
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", encode(key.borrow()), encode(value.borrow())))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/**
+ * Decode an *application/x-www-form-urlencoded* string into its fields, in the order they appear.
+ * Empty pairs are skipped, and a pair without *=* has an empty value.
+ */
+pub fn form_decode(s: &str) -> Vec<(String, String)> {
+    s.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+fn encode(s: &str) -> String {
+    s.split(' ')
+        .map(|word| {
+            percent_encode_except(word, |c| c.is_ascii_alphanumeric() || b"*-._".contains(&c))
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+fn decode(s: &str) -> String {
+    percent_decode(&s.replace('+', " ")).into_owned()
+}
+
+#[test]
+fn test_form_urlencoded() -> Result<(), crate::Error> {
+    use crate::{Method, Request};
+
+    better_panic::install();
+
+    let fields = [("name", "Jane Doe"), ("q", "a+b=c&d/é"), ("empty", "")];
+    let encoded = form_encode(fields.iter().copied());
+
+    assert_eq!(encoded, "name=Jane+Doe&q=a%2Bb%3Dc%26d%2F%C3%A9&empty=");
+    assert_eq!(
+        form_decode(&encoded),
+        fields
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        form_decode("a&&b=1%zz"),
+        [
+            ("a".to_string(), String::new()),
+            ("b".to_string(), "1%zz".to_string())
+        ]
+    );
+
+    let mut buf = Vec::new();
+
+    Request::default()
+        .method(Method::POST)
+        .form(&fields)
+        .write_to(&mut buf)?;
+
+    let req = Request::parse(&buf)?;
+
+    assert_eq!(
+        req.headers.get("content-type"),
+        Some(&FORM_URLENCODED.to_string())
+    );
+    assert_eq!(req.form_fields(), Some(form_decode(&encoded)));
+    assert_eq!(Request::default().form_fields(), None);
+
+    Ok(())
+}
@@ -0,0 +1,471 @@
+#[cfg(feature = "chunks")]
+use crate::ChunkedBody;
+#[cfg(test)]
+use crate::Response;
+#[cfg(feature = "chunks")]
+use nom::character::streaming::crlf;
+use {
+    crate::{
+        parse::{Input, ParseResult},
+        CommonHeaders::*,
+        Error, Headers, Method, StatusCode, CHUNK_SIZE,
+    },
+    std::io::{self, ErrorKind::ConnectionAborted, Read},
+};
+
+/**
+ * The most bytes a status line, header section, chunk header or trailer section may take up,
+ * so that a server cannot make us buffer and re-parse an endless head.
+ */
+const MAX_HEAD_SIZE: usize = 64 << 10;
+
+/**
+ * The most bytes of an unwanted body that are read to reuse its stream. Closing the stream is cheaper
+ * than reading more, and a server cannot keep us reading forever.
+ */
+const MAX_DRAIN_SIZE: u64 = 64 << 10;
+
+/**
+ * How the end of a message body is determined, per RFC 7230 section 3.3.3.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BodyLength {
+    // The message has no body, regardless of its headers.
+    Empty,
+    // The body is exactly this many bytes long.
+    Fixed(usize),
+    // The body is sent with chunked transfer-coding.
+    #[cfg(feature = "chunks")]
+    Chunked,
+    // The body ends when the server closes the connection.
+    UntilClose,
+}
+
+impl BodyLength {
+    /**
+     * The length of the body of a response with *status* and *headers*, sent in reply to a *method* request.
+     */
+    pub(crate) fn of_response(
+        status: StatusCode,
+        headers: &Headers,
+        method: &Method,
+    ) -> Result<Self, Error> {
+        let no_body = *method == Method::HEAD
+            || status.is_informational()
+            || status == StatusCode::NoContent
+            || status == StatusCode::NotModified
+            // A successful CONNECT turns the connection into a tunnel.
+            || (*method == Method::CONNECT && status.is_success());
+
+        if no_body {
+            return Ok(BodyLength::Empty);
+        }
+
+        if headers.contains_key(&TransferEncoding.to_string()) {
+            // Only chunked is understood; any other transfer-coding would be passed on still applied.
+            let unsupported = headers
+                .get_all(&TransferEncoding.to_string())
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .find(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case("chunked"))
+                .map(str::to_ascii_lowercase);
+
+            if let Some(coding) = unsupported {
+                return Err(Error::UnsupportedEncoding(coding));
+            }
+
+            #[cfg(feature = "chunks")]
+            {
+                if headers.is_chunked() {
+                    return Ok(BodyLength::Chunked);
+                }
+            }
+
+            return Ok(BodyLength::UntilClose);
+        }
+
+        match Self::content_length(headers)? {
+            Some(len) => Ok(BodyLength::Fixed(len)),
+            None => Ok(BodyLength::UntilClose),
+        }
+    }
+
+    /**
+     * The value of the *CONTENT-LENGTH* header. Repeated values are allowed only if they are all identical.
+     */
+    fn content_length(headers: &Headers) -> Result<Option<usize>, Error> {
+        let mut len = None;
+
+        for value in headers.get_all(&ContentLength.to_string()) {
+            for value in value.split(',').map(str::trim) {
+                let invalid = || Error::InvalidContentLength(value.to_string());
+                let parsed = match value.bytes().all(|c| c.is_ascii_digit()) {
+                    true => value.parse().map_err(|_| invalid())?,
+                    false => return Err(invalid()),
+                };
+
+                match len {
+                    Some(len) if len != parsed => return Err(invalid()),
+                    _ => len = Some(parsed),
+                }
+            }
+        }
+
+        Ok(len)
+    }
+}
+
+/**
+ * Reads a message from a stream piece by piece, keeping whatever has been read but not yet parsed.
+ */
+pub(crate) struct MessageReader<R> {
+    stream: R,
+    buf: Vec<u8>,
+    // The number of bytes read from the stream so far.
+    pub(crate) received: usize,
+}
+
+impl<R: Read> MessageReader<R> {
+    pub(crate) fn new(stream: R) -> Self {
+        Self {
+            stream,
+            buf: Vec::new(),
+            received: 0,
+        }
+    }
+
+    /**
+     * Read from the stream into *buf*, returning 0 at the end of the stream.
+     */
+    fn read_stream(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = match self.stream.read(buf) {
+            Ok(len) => len,
+            // rustls reports a connection closed without a TLS close_notify as aborted.
+            Err(e) if e.kind() == ConnectionAborted => 0,
+            Err(e) => return Err(e.into()),
+        };
+
+        self.received += len;
+        Ok(len)
+    }
+
+    /**
+     * Read more data into the buffer, returning the number of bytes read or 0 at the end of the stream.
+     */
+    fn fill(&mut self) -> Result<usize, Error> {
+        let mut chunk = [0; CHUNK_SIZE];
+        let len = self.read_stream(&mut chunk)?;
+
+        self.buf.extend_from_slice(&chunk[..len]);
+        Ok(len)
+    }
+
+    /**
+     * Run a streaming *parser* over the buffer, reading more whenever it needs more input,
+     * and consume what it parsed. Fails with Error::HeadTooLarge if it needs more than *MAX_HEAD_SIZE* bytes.
+     */
+    pub(crate) fn parse<T, F>(&mut self, parser: F) -> Result<T, Error>
+    where
+        F: for<'i> Fn(Input<'i>) -> ParseResult<'i, T>,
+    {
+        loop {
+            match parser(&self.buf) {
+                Ok((rest, value)) => {
+                    let used = self.buf.len() - rest.len();
+                    self.buf.drain(..used);
+                    return Ok(value);
+                }
+                Err(nom::Err::Incomplete(_)) if self.buf.len() >= MAX_HEAD_SIZE => {
+                    return Err(Error::HeadTooLarge(MAX_HEAD_SIZE))
+                }
+                Err(nom::Err::Incomplete(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            if self.fill()? == 0 {
+                return Err(Error::Incomplete);
+            }
+        }
+    }
+
+    /**
+     * Consume up to *buf.len()* bytes, from the buffer if it has any or else straight from the stream.
+     */
+    fn read_some(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.buf.is_empty() {
+            return self.read_stream(buf);
+        }
+
+        let len = buf.len().min(self.buf.len());
+
+        buf[..len].copy_from_slice(&self.buf[..len]);
+        self.buf.drain(..len);
+        Ok(len)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // This many bytes of a fixed-length body are left.
+    Fixed(usize),
+    // The next chunk starts here.
+    #[cfg(feature = "chunks")]
+    ChunkHeader,
+    // This many bytes of the current chunk are left, followed by a CRLF.
+    #[cfg(feature = "chunks")]
+    ChunkData(usize),
+    UntilClose,
+    Done,
+}
+
+/**
+ * The body of a message as it is read from the stream, with its transfer-coding removed.
+ * Once the body has been read to its end, the stream can be handed back for another message.
+ */
+pub(crate) struct RawBody<'c, R> {
+    reader: Option<MessageReader<R>>,
+    state: State,
+    // Whether the message has a body at all, even an empty one.
+    pub(crate) has_body: bool,
+    pub(crate) trailers: Headers,
+    // The number of body bytes read so far, before any content-codings are removed.
+    pub(crate) read: u64,
+    on_done: Option<Box<dyn FnOnce(R) + 'c>>,
+}
+
+impl<'c, R: Read> RawBody<'c, R> {
+    pub(crate) fn new(reader: MessageReader<R>, len: BodyLength) -> Self {
+        let state = match len {
+            BodyLength::Empty | BodyLength::Fixed(0) => State::Done,
+            BodyLength::Fixed(len) => State::Fixed(len),
+            #[cfg(feature = "chunks")]
+            BodyLength::Chunked => State::ChunkHeader,
+            BodyLength::UntilClose => State::UntilClose,
+        };
+
+        Self {
+            reader: Some(reader),
+            state,
+            has_body: len != BodyLength::Empty,
+            trailers: Headers::new(),
+            read: 0,
+            on_done: None,
+        }
+    }
+
+    /**
+     * Hand the stream to *f* once the body has been read to its end.
+     */
+    pub(crate) fn on_done(&mut self, f: impl FnOnce(R) + 'c) {
+        self.on_done = Some(Box::new(f));
+        self.release();
+    }
+
+    /**
+     * Read and discard the rest of the body, so the stream can be reused.
+     * Bodies that end when the connection closes, or that go on for more than *MAX_DRAIN_SIZE* bytes,
+     * are left alone, so the stream is dropped rather than reused.
+     */
+    pub(crate) fn drain(&mut self) {
+        if self.state != State::UntilClose {
+            let _ = io::copy(&mut self.take(MAX_DRAIN_SIZE), &mut io::sink());
+        }
+    }
+
+    /**
+     * Whether the body has been read to its end, reading ahead to find out without consuming any of its data.
+     * On a fresh body, this tells whether it is empty.
+     */
+    pub(crate) fn at_end(&mut self) -> Result<bool, Error> {
+        loop {
+            let reader = match &mut self.reader {
+                Some(reader) => reader,
+                None => return Ok(true),
+            };
+
+            match self.state {
+                State::Done => return Ok(true),
+                State::Fixed(_) => return Ok(false),
+                #[cfg(feature = "chunks")]
+                State::ChunkHeader => match reader.parse(ChunkedBody::parse_chunk_header)? {
+                    0 => {
+                        self.trailers = reader.parse(Headers::parse)?;
+                        self.finish();
+                    }
+                    size => self.state = State::ChunkData(size),
+                },
+                #[cfg(feature = "chunks")]
+                State::ChunkData(0) => {
+                    reader.parse(|i| crlf(i).map(|(i, _)| (i, ())))?;
+                    self.state = State::ChunkHeader;
+                }
+                #[cfg(feature = "chunks")]
+                State::ChunkData(_) => return Ok(false),
+                State::UntilClose => {
+                    if !reader.buf.is_empty() || reader.fill()? > 0 {
+                        return Ok(false);
+                    }
+                    self.finish();
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.state = State::Done;
+        self.release();
+    }
+
+    fn release(&mut self) {
+        let unread = self
+            .reader
+            .as_ref()
+            .is_some_and(|reader| !reader.buf.is_empty());
+
+        if self.state != State::Done || unread || self.on_done.is_none() {
+            return;
+        }
+
+        if let (Some(reader), Some(on_done)) = (self.reader.take(), self.on_done.take()) {
+            on_done(reader.stream);
+        }
+    }
+
+    // Only chunked bodies need more than one pass, to read the framing around their data.
+    #[cfg_attr(not(feature = "chunks"), allow(clippy::never_loop))]
+    fn read_body(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let reader = match &mut self.reader {
+                Some(reader) => reader,
+                None => return Ok(0),
+            };
+
+            match self.state {
+                State::Done => return Ok(0),
+                State::Fixed(left) => {
+                    let max = left.min(buf.len());
+                    let len = reader.read_some(&mut buf[..max])?;
+
+                    if len == 0 {
+                        return Err(Error::Incomplete);
+                    }
+
+                    self.state = State::Fixed(left - len);
+
+                    if left == len {
+                        self.finish();
+                    }
+                    return Ok(len);
+                }
+                #[cfg(feature = "chunks")]
+                State::ChunkHeader => match reader.parse(ChunkedBody::parse_chunk_header)? {
+                    0 => {
+                        self.trailers = reader.parse(Headers::parse)?;
+                        self.finish();
+                        return Ok(0);
+                    }
+                    size => self.state = State::ChunkData(size),
+                },
+                #[cfg(feature = "chunks")]
+                State::ChunkData(0) => {
+                    reader.parse(|i| crlf(i).map(|(i, _)| (i, ())))?;
+                    self.state = State::ChunkHeader;
+                }
+                #[cfg(feature = "chunks")]
+                State::ChunkData(left) => {
+                    let max = left.min(buf.len());
+                    let len = reader.read_some(&mut buf[..max])?;
+
+                    if len == 0 {
+                        return Err(Error::Incomplete);
+                    }
+
+                    self.state = State::ChunkData(left - len);
+                    return Ok(len);
+                }
+                State::UntilClose => {
+                    let len = reader.read_some(buf)?;
+
+                    if len == 0 {
+                        self.finish();
+                    }
+                    return Ok(len);
+                }
+            }
+        }
+    }
+}
+
+impl<'c, R: Read> Read for RawBody<'c, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.read_body(buf).map_err(io::Error::from)?;
+
+        self.read += len as u64;
+        Ok(len)
+    }
+}
+
+#[test]
+fn test_read_framed_response() -> Result<(), Error> {
+    better_panic::install();
+
+    let mut stream: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n\
+        HTTP/1.1 200 OK\r\nContent-Length: 5, 5\r\n\r\nhello";
+    let res = Response::read_from(&mut stream, &Method::GET)?;
+
+    assert_eq!(res.status.status_code, StatusCode::Success);
+    assert_eq!(res.body.unwrap().as_bytes(), b"hello");
+
+    let mut stream: &[u8] = b"HTTP/1.1 304 Not Modified\r\nContent-Length: 5\r\n\r\n";
+    assert!(Response::read_from(&mut stream, &Method::GET)?
+        .body
+        .is_none());
+
+    let mut stream: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n";
+    assert!(Response::read_from(&mut stream, &Method::HEAD)?
+        .body
+        .is_none());
+
+    let mut stream: &[u8] = b"HTTP/1.0 200 OK\r\n\r\nuntil close";
+    let res = Response::read_from(&mut stream, &Method::GET)?;
+    assert_eq!(res.body.unwrap().as_bytes(), b"until close");
+
+    let mut stream: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhel";
+    assert!(matches!(
+        Response::read_from(&mut stream, &Method::GET),
+        Err(Error::Incomplete)
+    ));
+
+    let mut stream: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5, 6\r\n\r\nhello";
+    assert!(matches!(
+        Response::read_from(&mut stream, &Method::GET),
+        Err(Error::InvalidContentLength(_))
+    ));
+
+    let mut stream: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n";
+    assert!(matches!(
+        Response::read_from(&mut stream, &Method::GET),
+        Err(Error::UnsupportedEncoding(coding)) if coding == "gzip"
+    ));
+
+    let mut endless = b"HTTP/1.1 200 OK\r\n".to_vec();
+    while endless.len() <= 2 * MAX_HEAD_SIZE {
+        endless.extend_from_slice(b"X-Padding: 0123456789\r\n");
+    }
+    assert!(matches!(
+        Response::read_from(&mut &endless[..], &Method::GET),
+        Err(Error::HeadTooLarge(MAX_HEAD_SIZE))
+    ));
+
+    let mut stream: &[u8] = b"";
+    assert!(matches!(
+        Response::read_from(&mut stream, &Method::GET),
+        Err(Error::ConnectionClosed)
+    ));
+
+    Ok(())
+}
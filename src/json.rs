@@ -0,0 +1,84 @@
+use {
+    crate::{Body, CommonHeaders::*, Error, Request, Response, SinglePartBody},
+    serde::{de::DeserializeOwned, Serialize},
+};
+
+/**
+ * The media type of a JSON body.
+ */
+const APPLICATION_JSON: &str = "application/json";
+
+impl<'a> Request<'a> {
+    /**
+     * Send *value* serialized as JSON, with *CONTENT-TYPE: application/json*.
+     */
+    pub fn json<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<&mut Self, Error> {
+        let data = serde_json::to_vec(value).map_err(Error::Json)?;
+
+        self.headers
+            .insert(&ContentType.to_string(), APPLICATION_JSON);
+        self.body = Some(Body::Single(SinglePartBody { data }));
+        self.body_reader = None;
+        Ok(self)
+    }
+}
+
+impl Response {
+    /**
+     * Deserialize the body as JSON. A response without a body is treated as empty,
+     * and fails with Error::Json like any other malformed JSON.
+     */
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        let data = self.body.as_ref().map(Body::as_bytes).unwrap_or_default();
+
+        serde_json::from_slice(data).map_err(Error::Json)
+    }
+}
+
+#[test]
+fn test_json_bodies() -> Result<(), Error> {
+    use {crate::Method, serde::Deserialize, std::collections::HashMap};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: u32,
+        tags: Vec<String>,
+    }
+
+    better_panic::install();
+
+    let item = Item {
+        id: 7,
+        tags: vec!["a".to_string(), "b".to_string()],
+    };
+    let mut buf = Vec::new();
+
+    Request::default()
+        .method(Method::POST)
+        .json(&item)?
+        .write_to(&mut buf)?;
+
+    let req = Request::parse(&buf)?;
+
+    assert_eq!(
+        req.headers.get("content-type"),
+        Some(&APPLICATION_JSON.to_string())
+    );
+    assert_eq!(
+        req.body.unwrap().as_bytes(),
+        br#"{"id":7,"tags":["a","b"]}"#
+    );
+
+    let mut stream: &[u8] =
+        b"HTTP/1.1 200 OK\r\nContent-Length: 25\r\n\r\n{\"id\":7,\"tags\":[\"a\",\"b\"]}";
+    let res = Response::read_from(&mut stream, &Method::GET)?;
+
+    assert_eq!(res.json::<Item>()?, item);
+
+    match res.json::<HashMap<String, u32>>() {
+        Err(Error::Json(e)) => assert_eq!((e.line(), e.column()), (1, 15)),
+        res => panic!("Expected a JSON error, got {:?}", res),
+    }
+
+    Ok(())
+}
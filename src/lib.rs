@@ -1,44 +1,138 @@
-mod parse;
+mod auth;
+#[cfg(feature = "chunks")]
+mod chunks;
+mod client;
+mod connection;
+mod connector;
+mod cookie;
+mod decoder;
+mod encoder;
+mod error;
+mod form;
+mod framing;
+#[cfg(feature = "json")]
+mod json;
+mod multipart;
+pub mod parse;
+mod proxy;
+mod redirect;
+mod socks;
+mod status;
+mod stream;
+mod suffix;
+mod timeout;
+mod upload;
+mod url;
+
+pub use auth::DigestAuth;
+#[cfg(feature = "chunks")]
+pub use chunks::ChunkedBody;
+pub use client::Client;
+#[cfg(unix)]
+pub use connector::UnixConnector;
+pub use connector::{Connector, MemoryConnector, TcpConnector, TlsConnector, Transport};
+pub use cookie::{Cookie, CookieJar, SameSite};
+pub use decoder::{DecompressionLimit, DecompressionLimits};
+pub use encoder::Encoding;
+pub use error::Error;
+pub use form::{form_decode, form_encode};
+pub use multipart::{MultiPartBody, Part};
+pub use proxy::{Proxy, ProxyPolicy, ProxyProtocol};
+pub use redirect::RedirectPolicy;
+pub use status::StatusCode;
+pub use stream::StreamingResponse;
+pub use suffix::PublicSuffixList;
+pub use timeout::{Timeout, Timeouts};
+pub use upload::BodyReader;
+pub use url::{percent_decode, percent_encode, Url};
 
 use {
-    cookie_factory::{combinator::string, gen, multi::all, sequence::tuple, SerializeFn},
-    flate2::bufread::{DeflateDecoder, GzDecoder, ZlibDecoder},
+    connection::default_tls_config,
+    connector::default_connector,
+    cookie_factory::{
+        combinator::{cond, slice, string},
+        gen,
+        multi::all,
+        sequence::tuple,
+        SerializeFn,
+    },
+    decoder::{accept_encoding, Decoder},
+    form::FORM_URLENCODED,
     nom::{
         bytes::{
-            streaming::take_till,
-            streaming::{tag, take_while},
-        },
-        character::{
-            is_alphabetic,
-            streaming::{crlf, digit1, space1},
+            streaming::{tag, take_while1},
+            streaming::{take, take_till},
         },
+        character::streaming::{crlf, space0, space1},
         error::context,
         multi::many_till,
         sequence::{preceded, terminated},
     },
-    parse::{Input, ParseResult},
-    rustls::{ClientConfig, ClientSession, Stream},
+    parse::{as_str, is_tchar, Input, ParseResult},
     std::{
-        collections::HashMap,
-        error::Error,
+        borrow::Cow,
+        collections::hash_map::RandomState,
         fmt::{Debug, Display},
-        io::{self, ErrorKind::ConnectionAborted},
+        fs::File,
+        hash::{BuildHasher, Hasher},
+        io,
         io::{Read, Write},
-        net::TcpStream,
-        str::{from_utf8, from_utf8_unchecked, FromStr},
+        str::{from_utf8_unchecked, FromStr},
         string::ToString,
-        sync::Arc,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     },
     strum_macros::{Display, EnumString},
-    webpki::DNSNameRef,
-    webpki_roots::TLS_SERVER_ROOTS,
     CommonHeaders::*,
     CtrlChars::Colon,
     CtrlChars::CR,
 };
 
+#[cfg(test)]
+use std::{error::Error as StdError, str::from_utf8};
+
 // -------------- UTILS --------------------
 
+/**
+ * The maximum amount of body data written per chunk when sending with chunked transfer-encoding.
+ */
+const CHUNK_SIZE: usize = 8192;
+
+static NO_HEADERS: Headers = Headers {
+    headers: Vec::new(),
+};
+
+/**
+ * Generate 32 random hexadecimal digits, for values that must be hard to guess or unlikely to collide.
+ * They come from the operating system's random number generator. Only if that fails are they derived
+ * from the time and a counter instead, which keeps them unique but not unpredictable.
+ */
+pub(crate) fn random_hex() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut bytes = [0; 16];
+
+    if getrandom::fill(&mut bytes).is_err() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let mut hasher = RandomState::new().build_hasher();
+
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u128(nanos);
+        let high = hasher.finish();
+        hasher.write_u64(high);
+        bytes[..8].copy_from_slice(&high.to_be_bytes());
+        bytes[8..].copy_from_slice(&hasher.finish().to_be_bytes());
+    }
+
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 /**
  * Unicode Hexadecimal values for some common control characters.
  */
@@ -70,6 +164,22 @@ pub enum CommonHeaders {
     ContentLength,
     #[strum(serialize = "CONTENT-ENCODING")]
     ContentEncoding,
+    #[strum(serialize = "TRANSFER-ENCODING")]
+    TransferEncoding,
+    #[strum(serialize = "AUTHORIZATION")]
+    Authorization,
+    #[strum(serialize = "LOCATION")]
+    Location,
+    #[strum(serialize = "WWW-AUTHENTICATE")]
+    WwwAuthenticate,
+    #[strum(serialize = "COOKIE")]
+    Cookie,
+    #[strum(serialize = "SET-COOKIE")]
+    SetCookie,
+    #[strum(serialize = "PROXY-AUTHORIZATION")]
+    ProxyAuthorization,
+    #[strum(serialize = "CONTENT-DISPOSITION")]
+    ContentDisposition,
 }
 
 /**
@@ -86,7 +196,39 @@ pub fn serialize_space<'a, W: io::Write + 'a>() -> impl SerializeFn<W> + 'a {
     string(" ")
 }
 
-#[derive(Display, Debug, PartialEq)]
+/**
+ * Serialize to binary a single header line, including the trailing CRLF.
+ */
+pub fn serialize_header<'a, W, K, V>(key: K, val: V) -> impl SerializeFn<W> + 'a
+where
+    W: io::Write + 'a,
+    K: AsRef<str> + 'a,
+    V: AsRef<str> + 'a,
+{
+    tuple((string(key), string(": "), string(val), serialize_crlf()))
+}
+
+/**
+ * Serialize to binary a single chunk of a chunked body: its size in hex, then its data.
+ */
+pub fn serialize_chunk<'a, W: io::Write + 'a>(data: &'a [u8]) -> impl SerializeFn<W> + 'a {
+    tuple((
+        string(format!("{:X}", data.len())),
+        serialize_crlf(),
+        slice(data),
+        serialize_crlf(),
+    ))
+}
+
+/**
+ * Serialize to binary the zero-sized chunk that terminates a chunked body.
+ * It must be followed by the (possibly empty) trailer section and a final CRLF.
+ */
+pub fn serialize_last_chunk<'a, W: io::Write + 'a>() -> impl SerializeFn<W> + 'a {
+    tuple((string("0"), serialize_crlf()))
+}
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Protocol {
     // The default protocol for requests.
     HTTP,
@@ -94,102 +236,210 @@ pub enum Protocol {
     HTTPS,
 }
 
+impl Protocol {
+    /**
+     * The URL scheme for this protocol.
+     */
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Protocol::HTTP => "http",
+            Protocol::HTTPS => "https",
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Protocol::HTTP => 80,
+            Protocol::HTTPS => 443,
+        }
+    }
+}
+
 //-------------- REQUEST ------------------
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Request<'a> {
     pub method: Method,
     pub protocol: Protocol,
-    pub host: Option<&'a str>,
-    pub path: &'a str,
+    pub host: Option<Cow<'a, str>>,
+    pub path: Cow<'a, str>,
     pub port: Option<u16>,
     pub version: &'a str,
     pub headers: Headers,
     pub body: Option<Body>,
+    // A body read from a source as it is sent, used instead of *body*.
+    pub body_reader: Option<BodyReader>,
+    pub trailers: Headers,
+    pub redirect: Option<RedirectPolicy>,
+    pub timeouts: Timeouts,
+    // Advertise the supported content-codings, and decode the response body.
+    pub decompress: bool,
+    pub decompression_limits: DecompressionLimits,
+    // Credentials for answering a Digest challenge, see *Request::digest_auth*.
+    pub digest: Option<DigestAuth>,
+    // The proxy to send the request through, by default the one given by the environment.
+    pub proxy: ProxyPolicy,
+    // Opens the connection to the server, see *Request::connector*.
+    pub connector: Option<Arc<dyn Connector>>,
 }
 
-impl<'a> Request<'a> {
+impl<'a> Default for Request<'a> {
     /**
-    Send the request over HTTPS.
-    */
-    fn send_https(
-        mut socket: &TcpStream,
-        mut buf: &mut Vec<u8>,
-        host: &str,
-    ) -> Result<(), io::Error> {
-        let mut config = ClientConfig::new();
-        config
-            .root_store
-            .add_server_trust_anchors(&TLS_SERVER_ROOTS);
-
-        let config = Arc::new(config);
-        let dns = DNSNameRef::try_from_ascii_str(host).unwrap();
-        let mut client = ClientSession::new(&config, dns);
-        let mut tls_stream = Stream::new(&mut client, &mut socket);
-
-        tls_stream.write_all(&mut buf)?;
-        buf.clear();
-
-        if let Err(e) = tls_stream.read_to_end(&mut buf) {
-            if e.kind() != ConnectionAborted {
-                return Err(e);
-            }
+     * Create a default request.
+     */
+    fn default() -> Self {
+        Self {
+            method: Method::GET,
+            path: Cow::Borrowed("/"),
+            protocol: Protocol::HTTP,
+            host: None,
+            port: Some(80),
+            version: "HTTP/1.1",
+            headers: Headers::new(),
+            body: None,
+            body_reader: None,
+            trailers: Headers::new(),
+            redirect: None,
+            timeouts: Timeouts::default(),
+            decompress: true,
+            decompression_limits: DecompressionLimits::default(),
+            digest: None,
+            proxy: ProxyPolicy::default(),
+            connector: None,
         }
+    }
+}
 
-        Ok(())
+impl<'a> Request<'a> {
+    /**
+     * Send the request, following redirects if a redirect policy is set.
+     */
+    pub fn send(&self) -> Result<Response, Error> {
+        self.send_streaming()?.collect()
     }
 
-    fn send_http(mut socket: &TcpStream, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
-        socket.write_all(buf)?;
-        buf.clear();
-        socket.read_to_end(buf)?;
+    /**
+     * Send the request, returning as soon as the headers of the response have been read.
+     * The body is read from the connection on demand.
+     */
+    pub fn send_streaming(&self) -> Result<StreamingResponse<'static>, Error> {
+        let deadline = self.timeouts.deadline();
+        let send = |req: &Request| {
+            req.send_once(deadline)?
+                .authenticate(req, |req| req.send_once(deadline))
+        };
+        let res = send(self)?;
+        let res = match &self.redirect {
+            Some(policy) => res.follow_redirects(self, policy, send)?,
+            None => res,
+        };
 
-        Ok(())
+        res.decode_for(self)
     }
 
     /**
-     * Send the request.
+     * Send the request, without following redirects, failing if it is not done by *deadline*.
      */
-    pub fn send(&self) -> Result<Response, Box<dyn Error>> {
-        match self.host {
+    fn send_once(&self, deadline: Option<Instant>) -> Result<StreamingResponse<'static>, Error> {
+        match &self.host {
             Some(host) => {
-                let port = if let Some(port) = self.port { port } else { 80 };
-                let socket = TcpStream::connect(format!("{}:{}", host, port))?;
-                let buf = Vec::new();
-                let (mut buf, _) = gen(self.serialize(), buf)?;
-
-                if self.protocol == Protocol::HTTPS {
-                    Self::send_https(&socket, &mut buf, host)?;
-                } else {
-                    Self::send_http(&socket, &mut buf)?;
-                }
-
-                Ok(Response::parse(&mut buf).unwrap())
+                let port = self.port.unwrap_or_else(|| self.protocol.default_port());
+                let proxy = match &self.connector {
+                    Some(_) => None,
+                    None => self.proxy.resolve(self.protocol, host),
+                };
+                let connector = match &self.connector {
+                    Some(connector) => connector.clone(),
+                    None => default_connector(proxy.clone(), default_tls_config()),
+                };
+                let mut conn = connection::Connection::connect(
+                    connector.as_ref(),
+                    self.protocol,
+                    host,
+                    port,
+                    &self.timeouts,
+                    deadline,
+                )?;
+                let mut extra = Headers::new();
+                let absolute_form = self.forward_through(proxy.as_ref(), &mut extra);
+
+                self.write_with(&mut conn, &extra, absolute_form)?;
+                StreamingResponse::read_head(conn, &self.method)
             }
-            None => panic!("No URL provided."),
+            None => Err(Error::MissingHost),
         }
     }
 
     /**
-     * Create a default request.
+     * Create a request for an absolute URL, configuring its protocol, host, port and path.
+     * Credentials in the URL are sent with basic authentication.
      */
-    pub fn default() -> Self {
-        Self {
-            method: Method::GET,
-            path: "/",
-            protocol: Protocol::HTTP,
-            host: None,
-            port: Some(80),
-            version: "HTTP/1.1",
-            headers: Headers::new(),
-            body: None,
+    pub fn from_url(url: &'a str) -> Result<Self, Error> {
+        let url = Url::parse(url)?;
+        let mut req = Self {
+            protocol: url.protocol,
+            port: Some(url.port_or_default()),
+            host: Some(Cow::Borrowed(url.host)),
+            path: url.target(),
+            ..Self::default()
+        };
+
+        req.headers.insert(&Host.to_string(), &url.authority());
+
+        if let Some(username) = url.username {
+            let password = url.password.map(percent_decode).unwrap_or_default();
+            req.basic_auth(&percent_decode(username), &password);
         }
+
+        Ok(req)
+    }
+
+    /**
+     * Follow redirects according to *policy* when sending this request.
+     */
+    pub fn redirect(&mut self, policy: RedirectPolicy) -> &mut Self {
+        self.redirect = Some(policy);
+        self
+    }
+
+    /**
+     * Whether to advertise the supported content-codings in *ACCEPT-ENCODING* and decode the response body.
+     * This is enabled by default; when disabled, the body is returned as it was sent.
+     */
+    pub fn decompress(&mut self, decompress: bool) -> &mut Self {
+        self.decompress = decompress;
+        self
+    }
+
+    /**
+     * Fail with Error::DecompressionLimit if the response body decodes to more than *limits* allow.
+     * By default, bodies may not decode to more than 200 times their encoded size once 1 MiB has been decoded.
+     */
+    pub fn decompression_limits(&mut self, limits: DecompressionLimits) -> &mut Self {
+        self.decompression_limits = limits;
+        self
+    }
+
+    /**
+     * Fail with Error::Timeout if the connection cannot be established within *timeout*.
+     */
+    pub fn connect_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeouts.connect = Some(timeout);
+        self
     }
 
     /**
-     * Notify the server that this request contains basic authentication.
+     * Fail with Error::Timeout if the server sends or accepts no data for *timeout*.
      */
-    pub fn basic_auth(&mut self, username: &'a str, password: &'a str) -> &mut Self {
-        self.header(username, password);
+    pub fn read_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeouts.read = Some(timeout);
+        self
+    }
+
+    /**
+     * Fail with Error::Timeout if the whole exchange, including redirects, takes longer than *timeout*.
+     */
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeouts.total = Some(timeout);
         self
     }
 
@@ -205,7 +455,7 @@ impl<'a> Request<'a> {
      * Set the host of the request.
      */
     pub fn host(&mut self, host: &'a str) -> &mut Self {
-        self.host = Some(host);
+        self.host = Some(Cow::Borrowed(host));
         self.header("HOST", host);
         self
     }
@@ -239,7 +489,7 @@ impl<'a> Request<'a> {
      * Set the server path.
      */
     pub fn path(&mut self, path: &'a str) -> &mut Self {
-        self.path = path;
+        self.path = Cow::Borrowed(path);
         self
     }
 
@@ -251,39 +501,150 @@ impl<'a> Request<'a> {
         self
     }
 
+    /**
+     * Send the request's body with chunked transfer-encoding instead of a *CONTENT-LENGTH*.
+     */
+    pub fn chunked(&mut self) -> &mut Self {
+        self.header("TRANSFER-ENCODING", "chunked");
+        self
+    }
+
+    /**
+     * Add a trailer to this request. Trailers are only sent with chunked bodies.
+     */
+    pub fn trailer(&mut self, key: &'a str, val: &'a str) -> &mut Self {
+        self.trailers.insert(key, val);
+        self
+    }
+
     /**
      * Set the request's body.
      */
     pub fn body(&mut self, i: Input<'a>) -> &mut Self {
         self.body = Body::parse(i, None).ok().map(|(_, body)| body);
+        self.body_reader = None;
+        self
+    }
+
+    /**
+     * Read the request's body from *reader* while it is sent, instead of holding it in memory.
+     * A body of known *len* is sent with a *CONTENT-LENGTH*, and any other with chunked transfer-encoding.
+     * Such a body can only be sent once, so redirects that would resend it are not followed.
+     */
+    pub fn body_reader(&mut self, reader: Box<dyn Read + Send>, len: Option<u64>) -> &mut Self {
+        self.body_reader = Some(BodyReader::new(reader, len));
+        self.body = None;
+        self
+    }
+
+    /**
+     * Send the contents of *file* as the request's body, without loading it into memory.
+     */
+    pub fn file(&mut self, file: File) -> &mut Self {
+        let len = file
+            .metadata()
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len());
+
+        self.body_reader(Box::new(file), len)
+    }
+
+    /**
+     * Send *fields* as an *application/x-www-form-urlencoded* body.
+     */
+    pub fn form(&mut self, fields: &[(&str, &str)]) -> &mut Self {
+        let data = form_encode(fields.iter().copied()).into_bytes();
+
+        self.headers
+            .insert(&ContentType.to_string(), FORM_URLENCODED);
+        self.body = Some(Body::Single(SinglePartBody { data }));
+        self.body_reader = None;
+        self
+    }
+
+    /**
+     * Decode the fields of an *application/x-www-form-urlencoded* body, in the order they were sent.
+     * Returns None if the request has no body, or its *CONTENT-TYPE* is not a form.
+     */
+    pub fn form_fields(&self) -> Option<Vec<(String, String)>> {
+        let content_type = self.headers.get(&ContentType.to_string())?;
+        let media = content_type.split(';').next().unwrap_or_default().trim();
+
+        if !media.eq_ignore_ascii_case(FORM_URLENCODED) {
+            return None;
+        }
+
+        let body = self.body.as_ref()?;
+
+        Some(form_decode(&String::from_utf8_lossy(body.as_bytes())))
+    }
+
+    /**
+     * Send *body* as the request's body, with the *CONTENT-TYPE* that describes its boundary.
+     */
+    pub fn multipart(&mut self, body: MultiPartBody) -> &mut Self {
+        self.headers
+            .insert(&ContentType.to_string(), &body.content_type());
+        self.body = Some(Body::Multi(body));
+        self.body_reader = None;
         self
     }
 
+    /**
+     * Compress the request's body with *encoding*, adding it to *CONTENT-ENCODING*.
+     * An in-memory body is compressed straight away and sent with its compressed length,
+     * while a streamed body is compressed as it is sent, with chunked transfer-encoding.
+     * Only the body set so far is compressed, so this should be called after setting it.
+     */
+    pub fn compress(&mut self, encoding: Encoding) -> Result<&mut Self, Error> {
+        if let Some(reader) = &self.body_reader {
+            self.body_reader = Some(reader.encode(encoding)?);
+        } else if let Some(body) = &self.body {
+            let mut data = Vec::new();
+
+            encoding
+                .encode(io::Cursor::new(body.as_bytes().to_vec()))?
+                .read_to_end(&mut data)?;
+            self.body = Some(Body::Single(SinglePartBody { data }));
+        } else {
+            return Ok(self);
+        }
+
+        self.headers.remove(&ContentLength.to_string());
+        self.headers
+            .append(&ContentEncoding.to_string(), encoding.name());
+        Ok(self)
+    }
+
     /**
      * Parse a request from a stream of bytes.
      */
-    pub fn parse(i: Input<'a>) -> Result<Self, Box<dyn Error + 'a>> {
+    pub fn parse(i: Input<'a>) -> Result<Self, Error> {
         let (_, req) = context("Request", |i| {
             let (i, method) = Method::parse(i)?;
             let (i, path) = preceded(space1, take_till(|c| c == CtrlChars::Space as u8))(i)?;
             let (i, version) = preceded(space1, terminated(take_till(|c| c == CR as u8), crlf))(i)?;
             let (i, headers) = Headers::parse(i)?;
-            let (i, body) = match headers.get(&ContentLength.to_string()) {
-                Some(len) => {
-                    let (i, body) = Body::parse(i, len.parse().ok())?;
-                    (i, Some(body))
-                }
-                None => (i, None),
-            };
+            let (i, (body, trailers)) = Body::parse_message(i, &headers)?;
             let res = Self {
                 method,
-                path: from_utf8(path).unwrap(),
+                path: Cow::Borrowed(as_str(path)?),
                 protocol: Protocol::HTTP,
                 host: None,
                 port: None,
-                version: from_utf8(version).unwrap(),
+                version: as_str(version)?,
                 headers,
                 body,
+                body_reader: None,
+                trailers,
+                redirect: None,
+                timeouts: Timeouts::default(),
+                decompress: false,
+                decompression_limits: DecompressionLimits::default(),
+                digest: None,
+                proxy: ProxyPolicy::default(),
+                connector: None,
             };
 
             Ok((i, res))
@@ -292,21 +653,150 @@ impl<'a> Request<'a> {
         Ok(req)
     }
 
+    /**
+     * The *CONTENT-LENGTH* to send with this request, if one must be computed.
+     * Returns None when there is no body, when the caller already set the header,
+     * or when the body is sent with chunked transfer-encoding.
+     */
+    fn content_length(&self) -> Option<u64> {
+        if self.headers.contains_key(&ContentLength.to_string()) || self.is_chunked() {
+            return None;
+        }
+
+        match &self.body_reader {
+            Some(reader) => reader.len(),
+            None => self.body.as_ref().map(|body| body.len() as u64),
+        }
+    }
+
+    /**
+     * Whether the body is sent with chunked transfer-encoding, either as requested
+     * or because it is read from a source of unknown length.
+     */
+    fn is_chunked(&self) -> bool {
+        self.headers.is_chunked() || self.needs_chunking()
+    }
+
+    /**
+     * Whether the *TRANSFER-ENCODING* header must be added, because the length of the body is unknown.
+     */
+    fn needs_chunking(&self) -> bool {
+        self.body_reader
+            .as_ref()
+            .is_some_and(|reader| reader.len().is_none())
+            && !self.headers.contains_key(&ContentLength.to_string())
+            && !self.headers.contains_key(&TransferEncoding.to_string())
+    }
+
+    /**
+     * Write the request to *w*, reading a streamed body as it is written.
+     */
+    pub(crate) fn write_to<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        self.write_with(w, &NO_HEADERS, false)
+    }
+
+    /**
+     * Write the request to *w* like *write_to*, sending *extra* headers along with its own.
+     * In *absolute_form*, the request line holds the full URL, as a proxy expects.
+     */
+    pub(crate) fn write_with<W: Write>(
+        &self,
+        w: &mut W,
+        extra: &Headers,
+        absolute_form: bool,
+    ) -> Result<(), Error> {
+        let target = match self.url() {
+            Some(url) if absolute_form => Cow::Owned(format!(
+                "{}://{}{}",
+                url.protocol.scheme(),
+                url.authority(),
+                url.target()
+            )),
+            _ => Cow::Borrowed(self.path.as_ref()),
+        };
+        let (buf, _) = gen(self.serialize_with(&target, extra), Vec::new())?;
+
+        w.write_all(&buf)?;
+
+        if let Some(reader) = &self.body_reader {
+            reader.write_to(w, self.is_chunked(), &self.trailers)?;
+        }
+
+        w.flush()?;
+        Ok(())
+    }
+
+    #[cfg(test)]
     fn serialize<W: io::Write + 'a>(&'a self) -> impl SerializeFn<W> + 'a {
+        self.serialize_with(&self.path, &NO_HEADERS)
+    }
+
+    /**
+     * Serialize the request for *target*, with *extra* headers after its own.
+     * A streamed body is not included, only the headers that frame it.
+     */
+    fn serialize_with<W: io::Write + 'a>(
+        &'a self,
+        target: &'a str,
+        extra: &'a Headers,
+    ) -> impl SerializeFn<W> + 'a {
         tuple((
             self.method.serialize(),
             serialize_space(),
-            string(self.path),
+            string(target),
             serialize_space(),
             string(self.version),
             serialize_crlf(),
             self.headers.serialize(),
+            extra.serialize(),
+            all(self
+                .content_length()
+                .into_iter()
+                .map(|len| serialize_header(ContentLength.to_string(), len.to_string()))),
+            cond(
+                self.needs_chunking(),
+                serialize_header(TransferEncoding.to_string(), "chunked"),
+            ),
+            all(self
+                .digest_authorization()
+                .into_iter()
+                .map(|auth| serialize_header(Authorization.to_string(), auth))),
+            cond(
+                self.decompress && !self.headers.contains_key(&AcceptEncoding.to_string()),
+                serialize_header(AcceptEncoding.to_string(), accept_encoding()),
+            ),
+            serialize_crlf(),
+            cond(
+                self.body_reader.is_none(),
+                tuple((
+                    cond(
+                        !self.is_chunked(),
+                        all(self.body.iter().map(Body::serialize)),
+                    ),
+                    cond(self.is_chunked(), self.serialize_chunked_body()),
+                )),
+            ),
+        ))
+    }
+
+    fn serialize_chunked_body<W: io::Write + 'a>(&'a self) -> impl SerializeFn<W> + 'a {
+        tuple((
+            all(self
+                .body
+                .iter()
+                .flat_map(|body| body.as_bytes().chunks(CHUNK_SIZE))
+                .map(serialize_chunk)),
+            serialize_last_chunk(),
+            self.trailers.serialize(),
             serialize_crlf(),
         ))
     }
 }
 
-#[derive(Debug, Display, PartialEq, EnumString, Eq)]
+/**
+ * Request methods. Methods other than the standard ones are carried by *Extension*.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Method {
     OPTIONS,
     GET,
@@ -316,25 +806,67 @@ pub enum Method {
     DELETE,
     TRACE,
     CONNECT,
+    PATCH,
+    Extension(String),
 }
 
 impl Method {
+    /**
+     * Create an extension method, such as *PROPFIND* or *PURGE*.
+     * The name must be a valid token; standard method names yield their own variant.
+     */
+    pub fn extension(name: &str) -> Result<Self, Error> {
+        name.parse()
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::OPTIONS => "OPTIONS",
+            Method::GET => "GET",
+            Method::HEAD => "HEAD",
+            Method::POST => "POST",
+            Method::PUT => "PUT",
+            Method::DELETE => "DELETE",
+            Method::TRACE => "TRACE",
+            Method::CONNECT => "CONNECT",
+            Method::PATCH => "PATCH",
+            Method::Extension(name) => name,
+        }
+    }
+
+    /**
+     * Safe methods are essentially read-only and can be retried or prefetched freely.
+     */
+    pub fn is_safe(&self) -> bool {
+        matches!(
+            self,
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        )
+    }
+
+    /**
+     * Idempotent methods have the same effect when sent once or many times,
+     * so a request using one can be retried after a connection failure.
+     * Extension methods are never assumed to be idempotent.
+     */
+    pub fn is_idempotent(&self) -> bool {
+        self.is_safe() || matches!(self, Method::PUT | Method::DELETE)
+    }
+
     pub fn serialize<'a, W: io::Write + 'a>(&'a self) -> impl SerializeFn<W> + 'a {
-        string(self.to_string())
+        string(self.as_str())
     }
 
     pub fn parse(i: Input) -> ParseResult<Self> {
         context("Method", |i| {
-            let (i, method) = take_while(is_alphabetic)(i)?;
-            let res = match Method::from_str(from_utf8(method).unwrap()) {
+            let (i, method) = take_while1(is_tchar)(i)?;
+            let res = match Method::from_str(as_str(method)?) {
                 Ok(method) => method,
-                Err(_) => {
-                    unsafe {
-                        panic!(
-                            "[UTF-8 decoding] Invalid HTTP method '{}'",
-                            from_utf8_unchecked(method)
-                        );
-                    };
+                Err(e) => {
+                    return Err(nom::Err::Failure(parse::Error::custom(
+                        method,
+                        e.to_string(),
+                    )));
                 }
             };
 
@@ -343,30 +875,77 @@ impl Method {
     }
 }
 
-#[derive(Debug)]
+impl FromStr for Method {
+    type Err = Error;
+
+    /**
+     * Method names are case-sensitive, so *get* is an extension method rather than *GET*.
+     */
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let method = match s {
+            "OPTIONS" => Method::OPTIONS,
+            "GET" => Method::GET,
+            "HEAD" => Method::HEAD,
+            "POST" => Method::POST,
+            "PUT" => Method::PUT,
+            "DELETE" => Method::DELETE,
+            "TRACE" => Method::TRACE,
+            "CONNECT" => Method::CONNECT,
+            "PATCH" => Method::PATCH,
+            _ if !s.is_empty() && s.bytes().all(is_tchar) => Method::Extension(s.to_string()),
+            _ => return Err(Error::InvalidMethod(s.to_string())),
+        };
+
+        Ok(method)
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Header {
     pub key: String,
     pub value: String,
 }
 
 impl Header {
+    /**
+     * Parse a single header line. The name and value are kept exactly as sent,
+     * apart from the optional whitespace around the value.
+     */
     pub fn parse(i: Input) -> ParseResult<Self> {
         context("Header", |i| {
-            let (i, key) = terminated(take_till(|c| c == Colon as u8), tag(b": "))(i)?;
-            let (i, value) = terminated(take_till(|c| c == CR as u8), crlf)(i)?;
+            let (i, key) = terminated(take_till(|c| c == Colon as u8), tag(b":"))(i)?;
+            let (i, value) = preceded(space0, terminated(take_till(|c| c == CR as u8), crlf))(i)?;
             let res = Self {
-                key: from_utf8(key).unwrap().to_string().to_uppercase(),
-                value: from_utf8(value).unwrap().to_string().to_uppercase(),
+                key: as_str(key)?.to_string(),
+                value: as_str(value)?.trim_end().to_string(),
             };
 
             Ok((i, res))
         })(i)
     }
+
+    /**
+     * Header names are compared case-insensitively.
+     */
+    pub fn is(&self, key: &str) -> bool {
+        self.key.eq_ignore_ascii_case(key)
+    }
 }
 
-#[derive(Debug)]
+/**
+ * An ordered collection of headers.
+ * Names are matched case-insensitively but sent with their original casing,
+ * in the order they were added. A name may appear more than once (e.g. *Set-Cookie*).
+ */
+#[derive(Debug, Clone)]
 pub struct Headers {
-    headers: HashMap<String, String>,
+    headers: Vec<Header>,
 }
 
 impl Headers {
@@ -374,62 +953,210 @@ impl Headers {
         all(self
             .headers
             .iter()
-            .map(|(key, val)| tuple((string(key), string(": "), string(val), serialize_crlf()))))
+            .map(|header| serialize_header(&header.key, &header.value)))
     }
 
     pub fn new() -> Self {
         Self {
-            headers: HashMap::new(),
+            headers: Vec::new(),
         }
     }
 
     pub fn parse(i: Input) -> ParseResult<Self> {
         context("Headers", |i| {
-            let (i, (data, _)) = many_till(Header::parse, crlf)(i)?;
-            let mut headers = HashMap::new();
-
-            for header in data {
-                headers.insert(header.key, header.value);
-            }
+            let (i, (headers, _)) = many_till(Header::parse, crlf)(i)?;
 
             Ok((i, Self { headers }))
         })(i)
     }
 
+    /**
+     * Set a header, replacing every existing value with the same name.
+     */
     pub fn insert(&mut self, key: &str, val: &str) {
-        self.headers.insert(key.to_string(), val.to_string());
-    }
+        let mut found = false;
 
-    pub fn get(&self, key: &str) -> Option<&String> {
-        self.headers.get(&key.to_string())
-    }
+        self.headers.retain_mut(|header| {
+            if !header.is(key) {
+                return true;
+            }
+            if found {
+                return false;
+            }
+            found = true;
+            header.value = val.to_string();
+            true
+        });
 
-    pub fn contains_key(&self, key: &str) -> bool {
-        self.headers.contains_key(key)
+        if !found {
+            self.append(key, val);
+        }
     }
-}
 
-#[derive(Display, Debug)]
-pub enum Body {
-    Single(SinglePartBody),
-    Multi(MultiPartBody),
-}
+    /**
+     * Add a header, keeping any existing values with the same name.
+     */
+    pub fn append(&mut self, key: &str, val: &str) {
+        self.headers.push(Header {
+            key: key.to_string(),
+            value: val.to_string(),
+        });
+    }
 
-impl Body {
     /**
-     * Parse a binary input into request body format.
-     * Takes a *len*, representing the *CONTENT-LENGTH* of the body.
-     * If len is None, then parse until the end of the input. Otherwise, only parse *len* amount of it.
+     * Remove every header with the given name, returning whether any were present.
      */
-    pub fn parse(i: Input, len: Option<usize>) -> ParseResult<Self> {
-        context("Body", |i: Input| {
-            let (i, body) = SinglePartBody::parse(i, len)?;
-            Ok((i, Body::Single(body)))
-        })(i)
+    pub fn remove(&mut self, key: &str) -> bool {
+        let len = self.headers.len();
+        self.headers.retain(|header| !header.is(key));
+        len != self.headers.len()
     }
-}
 
-pub struct SinglePartBody {
+    /**
+     * The first value of the header with the given name.
+     */
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.headers
+            .iter()
+            .find(|header| header.is(key))
+            .map(|header| &header.value)
+    }
+
+    /**
+     * Every value of the header with the given name, in the order they were added.
+     */
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.headers
+            .iter()
+            .filter(move |header| header.is(key))
+            .map(|header| &header.value)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.headers.iter().any(|header| header.is(key))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Header> {
+        self.headers.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /**
+     * Whether the final transfer-coding applied to the message is *chunked*.
+     */
+    pub fn is_chunked(&self) -> bool {
+        match self.get_all(&TransferEncoding.to_string()).last() {
+            Some(codings) => codings
+                .rsplit(',')
+                .next()
+                .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked")),
+            None => false,
+        }
+    }
+}
+
+impl Default for Headers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Display, Debug, Clone)]
+pub enum Body {
+    Single(SinglePartBody),
+    Multi(MultiPartBody),
+}
+
+impl Body {
+    /**
+     * The number of bytes this body occupies on the wire.
+     */
+    pub fn len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Body::Single(body) => &body.data,
+            Body::Multi(body) => &body.data,
+        }
+    }
+
+    pub fn serialize<'a, W: io::Write + 'a>(&'a self) -> impl SerializeFn<W> + 'a {
+        slice(self.as_bytes())
+    }
+
+    /**
+     * Parse a binary input into request body format.
+     * Takes a *len*, representing the *CONTENT-LENGTH* of the body.
+     * If len is None, then parse until the end of the input. Otherwise, only parse *len* amount of it.
+     */
+    pub fn parse(i: Input, len: Option<usize>) -> ParseResult<Self> {
+        context("Body", |i: Input| {
+            let (i, body) = SinglePartBody::parse(i, len)?;
+            Ok((i, Body::Single(body)))
+        })(i)
+    }
+
+    /**
+     * Split a single-part body into its parts if *headers* describe it as multipart.
+     * Bodies that still have a content-coding, or that are not well-formed, are left as they are.
+     */
+    pub fn split_multipart(self, headers: &Headers) -> Self {
+        let boundary = match MultiPartBody::boundary_of(headers) {
+            Some(boundary) if !headers.contains_key(&ContentEncoding.to_string()) => boundary,
+            _ => return self,
+        };
+
+        match &self {
+            Body::Single(body) => match MultiPartBody::parse(&body.data, &boundary) {
+                Ok((_, multi)) => Body::Multi(multi),
+                Err(_) => self,
+            },
+            Body::Multi(_) => self,
+        }
+    }
+
+    /**
+     * Parse the body of a message as framed by its *headers*.
+     * Returns the body, if there is one, along with the trailers sent after a chunked body.
+     */
+    pub fn parse_message<'a>(
+        i: Input<'a>,
+        headers: &Headers,
+    ) -> ParseResult<'a, (Option<Self>, Headers)> {
+        #[cfg(feature = "chunks")]
+        {
+            if headers.is_chunked() {
+                let (i, chunked) = ChunkedBody::parse(i)?;
+                let body = Body::Single(chunked.body).split_multipart(headers);
+                return Ok((i, (Some(body), chunked.trailers)));
+            }
+        }
+
+        match headers.get(&ContentLength.to_string()) {
+            Some(len) => {
+                let (i, body) = Body::parse(i, len.parse().ok())?;
+                Ok((i, (Some(body.split_multipart(headers)), Headers::new())))
+            }
+            None => Ok((i, (None, Headers::new()))),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SinglePartBody {
     data: Vec<u8>,
 }
 
@@ -446,40 +1173,55 @@ impl Debug for SinglePartBody {
 }
 
 impl SinglePartBody {
-    pub fn decompress(&mut self, headers: &Headers) -> Result<&Self, Box<dyn Error>> {
-        let old_buf = &self.data[..];
-        let mut new_buf = Vec::new();
-        let header = headers.get(&ContentEncoding.to_string());
-
-        match header.map(|h| &h[..]) {
-            Some("GZIP") => {
-                let mut decoder = GzDecoder::new(old_buf);
-                decoder.read_to_end(&mut new_buf)?;
-                self.data = new_buf;
-            }
-            Some("DEFLATE") => {
-                let mut decoder = DeflateDecoder::new(old_buf);
-                decoder.read_to_end(&mut new_buf)?;
-                self.data = new_buf;
-            }
-            Some("ZLIB") => {
-                let mut decoder = ZlibDecoder::new(old_buf);
-                decoder.read_to_end(&mut new_buf)?;
-                self.data = new_buf;
+    /**
+     * Decode the body according to the *CONTENT-ENCODING* of *headers*, undoing stacked codings in reverse order.
+     * Empty bodies and bodies without a content-coding are left as they are. The default *DecompressionLimits* apply,
+     * which reject bodies that decode to more than 200 times their encoded size once 1 MiB has been decoded.
+     */
+    pub fn decompress(&mut self, headers: &Headers) -> Result<&Self, Error> {
+        self.decompress_with(headers, &DecompressionLimits::default())
+    }
+
+    /**
+     * Like *decompress*, but failing with Error::DecompressionLimit once the decoded body exceeds *limits*.
+     */
+    pub fn decompress_with(
+        &mut self,
+        headers: &Headers,
+        limits: &DecompressionLimits,
+    ) -> Result<&Self, Error> {
+        if self.data.is_empty() {
+            return Ok(self);
+        }
+
+        let mut decoder = Decoder::new(&self.data[..], headers)?;
+
+        if !decoder.is_identity() {
+            let mut data = Vec::new();
+            let mut buf = vec![0; CHUNK_SIZE];
+
+            loop {
+                let len = match decoder.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(len) => len,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(Error::Decompression(e)),
+                };
+
+                data.extend_from_slice(&buf[..len]);
+                limits.check(self.data.len() as u64, data.len() as u64)?;
             }
-            Some(_) => unimplemented!("Unknown compression schema."),
-            None => println!("Response body was not compressed"),
-        };
+            self.data = data;
+        }
 
         Ok(self)
     }
 
     pub fn parse(i: Input, len: Option<usize>) -> ParseResult<Self> {
         context("Single-part Body", |i: Input| {
-            let data = if let Some(len) = len {
-                &i[..len]
-            } else {
-                &i[..]
+            let (i, data) = match len {
+                Some(len) => take(len)(i)?,
+                None => (&i[i.len()..], i),
             };
             Ok((
                 i,
@@ -491,58 +1233,8 @@ impl SinglePartBody {
     }
 }
 
-#[derive(Debug)]
-pub struct MultiPartBody {
-    data: Vec<u8>,
-}
-
 // -------------------- RESPONSE---------------------
 
-#[derive(Debug, PartialEq, EnumString, Eq)]
-pub enum StatusCode {
-    Success = 200,
-    BadRequest = 400,
-    NotFound = 404,
-    MovedPermanently = 301,
-}
-
-impl StatusCode {
-    pub fn from_str(i: Input) -> Option<Self> {
-        use StatusCode::*;
-
-        unsafe {
-            match from_utf8_unchecked(i) {
-                "200" => Some(Success),
-                "400" => Some(BadRequest),
-                "404" => Some(NotFound),
-                "301" => Some(MovedPermanently),
-                _ => None,
-            }
-        }
-    }
-}
-
-impl StatusCode {
-    pub fn parse(i: Input) -> ParseResult<Self> {
-        context("Status Code", |i| {
-            let (i, status_code) = terminated(digit1, space1)(i)?;
-            let res = match StatusCode::from_str(status_code) {
-                Some(method) => method,
-                None => {
-                    unsafe {
-                        panic!(
-                            "[UTF-8 decoding] Invalid HTTP status code '{}'.",
-                            from_utf8_unchecked(status_code)
-                        );
-                    };
-                }
-            };
-
-            Ok((i, res))
-        })(i)
-    }
-}
-
 #[derive(Debug)]
 pub struct ResponseStatus {
     pub protocol_version: String,
@@ -558,9 +1250,9 @@ impl ResponseStatus {
             let (i, status_code) = StatusCode::parse(i)?;
             let (i, description) = terminated(take_till(|c| c == CR as u8), crlf)(i)?;
             let res = Self {
-                protocol_version: from_utf8(protocol_version).unwrap().to_string(),
+                protocol_version: as_str(protocol_version)?.to_string(),
                 status_code,
-                description: from_utf8(description).unwrap().to_string(),
+                description: as_str(description)?.to_string(),
             };
 
             Ok((i, res))
@@ -573,24 +1265,23 @@ pub struct Response {
     pub status: ResponseStatus,
     pub headers: Headers,
     pub body: Option<Body>,
+    pub trailers: Headers,
+    // The URLs of the redirects that were followed to get this response, in order.
+    pub redirects: Vec<String>,
 }
 
 impl Response {
-    pub fn parse<'a>(i: Input<'a>) -> Result<Self, Box<dyn Error + 'a>> {
+    pub fn parse(i: Input) -> Result<Self, Error> {
         let (_, response) = context("Response", |i| {
             let (i, status) = ResponseStatus::parse(i)?;
             let (i, headers) = Headers::parse(i)?;
-            let (i, body) = match headers.get(&ContentLength.to_string()) {
-                Some(len) => {
-                    let (i, body) = Body::parse(i, len.parse().ok())?;
-                    (i, Some(body))
-                }
-                None => (i, None),
-            };
+            let (i, (body, trailers)) = Body::parse_message(i, &headers)?;
             let res = Self {
                 status,
                 headers,
                 body,
+                trailers,
+                redirects: Vec::new(),
             };
 
             Ok((i, res))
@@ -601,7 +1292,7 @@ impl Response {
 }
 
 #[test]
-fn test_parse_request() -> Result<(), Box<dyn Error>> {
+fn test_parse_request() -> Result<(), Box<dyn StdError>> {
     better_panic::install();
 
     let mut req_str = String::new();
@@ -629,7 +1320,7 @@ fn test_parse_request() -> Result<(), Box<dyn Error>> {
 }
 
 #[test]
-fn test_parse_response() -> Result<(), Box<dyn Error>> {
+fn test_parse_response() -> Result<(), Box<dyn StdError>> {
     better_panic::install();
     let connection_header = Connection.to_string();
     let accept_encoding = AcceptEncoding.to_string();
@@ -651,11 +1342,11 @@ fn test_parse_response() -> Result<(), Box<dyn Error>> {
     assert_eq!(res.status.description, "OK");
     assert_eq!(
         res.headers.get(&Connection.to_string()),
-        Some(&String::from("CLOSE"))
+        Some(&String::from("close"))
     );
     assert_eq!(
         res.headers.get(&ContentType.to_string()),
-        Some(&String::from("APPLICATION/JSON"))
+        Some(&String::from("application/json"))
     );
 
     match &mut res.body {
@@ -663,7 +1354,15 @@ fn test_parse_response() -> Result<(), Box<dyn Error>> {
             Body::Single(ref mut body) => {
                 body.decompress(&res.headers)?;
             }
-            Body::Multi(_) => unimplemented!(),
+            // Each part carries its own headers, and is decoded according to them.
+            Body::Multi(body) => {
+                for part in body.parts() {
+                    SinglePartBody {
+                        data: part.data.clone(),
+                    }
+                    .decompress(&part.headers)?;
+                }
+            }
         },
         None => panic!("No body for response."),
     };
@@ -672,3 +1371,514 @@ fn test_parse_response() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_serialize_request_body() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let mut req = Request::default();
+
+    req.method(Method::POST)
+        .path("/upload")
+        .host("localhost")
+        .body(b"hello world");
+
+    let (buf, _) = gen(req.serialize(), Vec::new())?;
+    let buf = from_utf8(&buf)?;
+
+    assert!(buf.starts_with("POST /upload HTTP/1.1\r\n"));
+    assert!(buf.contains("CONTENT-LENGTH: 11\r\n"));
+    assert!(buf.ends_with("\r\n\r\nhello world"));
+
+    let mut req = Request::default();
+
+    req.method(Method::PUT)
+        .header("CONTENT-LENGTH", "5")
+        .body(b"hello");
+
+    let (buf, _) = gen(req.serialize(), Vec::new())?;
+    let buf = from_utf8(&buf)?;
+
+    assert_eq!(buf.matches("CONTENT-LENGTH").count(), 1);
+    assert!(buf.ends_with("\r\n\r\nhello"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "chunks")]
+fn test_parse_chunked_response() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let mut res_str = String::new();
+
+    res_str.push_str("HTTP/1.1 200 OK\r\n");
+    res_str.push_str("Transfer-Encoding: chunked\r\n");
+    res_str.push_str("\r\n");
+    res_str.push_str("5\r\nhello\r\n");
+    res_str.push_str("6\r\n world\r\n");
+    res_str.push_str("0\r\n");
+    res_str.push_str("Checksum: abc\r\n");
+    res_str.push_str("\r\n");
+
+    let res = Response::parse(res_str.as_bytes()).unwrap();
+
+    match res.body {
+        Some(Body::Single(body)) => assert_eq!(body.to_string(), "hello world"),
+        _ => panic!("No body for response."),
+    }
+    assert_eq!(res.trailers.get("checksum"), Some(&String::from("abc")));
+
+    Ok(())
+}
+
+#[test]
+fn test_serialize_chunked_request() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let data = vec![b'a'; CHUNK_SIZE + 1];
+    let mut req = Request::default();
+
+    req.method(Method::POST)
+        .path("/logs")
+        .chunked()
+        .trailer("CHECKSUM", "abc")
+        .body(&data);
+
+    let (buf, _) = gen(req.serialize(), Vec::new())?;
+    let buf = from_utf8(&buf)?;
+    let (head, body) = buf.split_at(buf.find("\r\n\r\n").unwrap() + 4);
+
+    assert!(head.contains("TRANSFER-ENCODING: chunked\r\n"));
+    assert!(!head.contains("CONTENT-LENGTH"));
+    assert!(body.starts_with("2000\r\naaaa"));
+    assert!(body.ends_with("a\r\n1\r\na\r\n0\r\nCHECKSUM: abc\r\n\r\n"));
+
+    #[cfg(feature = "chunks")]
+    {
+        let (rest, chunked) = ChunkedBody::parse(body.as_bytes()).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(chunked.body.data, data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_request_body() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let data: &'static [u8] = b"streamed body";
+    let mut req = Request::default();
+    let mut buf = Vec::new();
+
+    req.method(Method::PUT)
+        .body_reader(Box::new(data), Some(data.len() as u64));
+    req.write_to(&mut buf)?;
+
+    let buf = from_utf8(&buf)?;
+
+    assert!(buf.contains("CONTENT-LENGTH: 13\r\n"));
+    assert!(buf.ends_with("\r\n\r\nstreamed body"));
+    assert!(matches!(
+        req.write_to(&mut Vec::new()),
+        Err(Error::BodyConsumed)
+    ));
+
+    let mut buf = Vec::new();
+
+    req.body_reader(Box::new(data), None)
+        .trailer("CHECKSUM", "abc");
+    req.write_to(&mut buf)?;
+
+    let buf = from_utf8(&buf)?;
+
+    assert!(buf.contains("TRANSFER-ENCODING: chunked\r\n"));
+    assert!(!buf.contains("CONTENT-LENGTH"));
+    assert!(buf.ends_with("\r\n\r\nD\r\nstreamed body\r\n0\r\nCHECKSUM: abc\r\n\r\n"));
+
+    req.body_reader(Box::new(&data[..4]), Some(data.len() as u64));
+    assert!(matches!(req.write_to(&mut Vec::new()), Err(Error::Io(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_compress_request_body() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let data = b"compressed body ".repeat(100);
+    let mut req = Request::default();
+    let mut buf = Vec::new();
+
+    req.method(Method::POST)
+        .body(&data)
+        .compress(Encoding::Gzip)?
+        .write_to(&mut buf)?;
+
+    let parsed = Request::parse(&buf)?;
+    let len = parsed.headers.get(&ContentLength.to_string()).unwrap();
+
+    assert_eq!(
+        parsed.headers.get(&ContentEncoding.to_string()),
+        Some(&"gzip".to_string())
+    );
+    assert!(len.parse::<usize>()? < data.len());
+    match parsed.body {
+        Some(Body::Single(mut body)) => {
+            body.decompress(&parsed.headers)?;
+            assert_eq!(body.data, data);
+        }
+        body => panic!("Expected a single-part body, got {:?}", body),
+    }
+
+    // A streamed body is sent chunked, which can only be parsed with the chunks feature.
+    #[cfg(feature = "chunks")]
+    {
+        let mut req = Request::default();
+        let mut buf = Vec::new();
+
+        req.body_reader(Box::new(io::Cursor::new(data.clone())), None)
+            .compress(Encoding::Deflate)?
+            .write_to(&mut buf)?;
+
+        let parsed = Request::parse(&buf)?;
+
+        assert!(parsed.headers.is_chunked());
+        assert_eq!(
+            parsed.headers.get(&ContentEncoding.to_string()),
+            Some(&"deflate".to_string())
+        );
+        match parsed.body {
+            Some(Body::Single(mut body)) => {
+                body.decompress(&parsed.headers)?;
+                assert_eq!(body.data, data);
+            }
+            body => panic!("Expected a single-part body, got {:?}", body),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_typed_errors() {
+    better_panic::install();
+
+    match Request::default().send() {
+        Err(Error::MissingHost) => {}
+        res => panic!("Expected a missing host error, got {:?}", res),
+    }
+
+    match Request::parse(b"(GET) / HTTP/1.1\r\n\r\n") {
+        Err(Error::Parse(errors)) => assert!(errors
+            .iter()
+            .any(|kind| matches!(kind, parse::ErrorKind::Context("Method")))),
+        res => panic!("Expected a parse error, got {:?}", res),
+    }
+
+    match Response::parse(b"HTTP/1.1 200 OK\r\nContent-") {
+        Err(Error::Incomplete) => {}
+        res => panic!("Expected an incomplete error, got {:?}", res),
+    }
+}
+
+#[test]
+fn test_extension_methods() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let req = Request::parse(b"PROPFIND /calendars HTTP/1.1\r\n\r\n")?;
+
+    assert_eq!(req.method, Method::Extension("PROPFIND".to_string()));
+    assert_eq!(Method::extension("PATCH")?, Method::PATCH);
+    assert_eq!(Method::extension("PURGE")?.to_string(), "PURGE");
+    assert!(matches!(
+        Method::extension("BAD METHOD"),
+        Err(Error::InvalidMethod(_))
+    ));
+    assert!(matches!(
+        Method::extension(""),
+        Err(Error::InvalidMethod(_))
+    ));
+
+    assert!(Method::HEAD.is_safe() && Method::HEAD.is_idempotent());
+    assert!(!Method::PUT.is_safe() && Method::PUT.is_idempotent());
+    assert!(!Method::PATCH.is_idempotent());
+    assert!(!Method::extension("MKCOL")?.is_idempotent());
+
+    Ok(())
+}
+
+#[test]
+fn test_headers() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let mut res_str = String::new();
+
+    res_str.push_str("HTTP/1.1 302 Found\r\n");
+    res_str.push_str("Location: /Login?Next=%2FHome\r\n");
+    res_str.push_str("Set-Cookie: a=Secret\r\n");
+    res_str.push_str("ETag:\"xYz\"  \r\n");
+    res_str.push_str("set-cookie: b=Token\r\n");
+    res_str.push_str("\r\n");
+
+    let mut headers = Response::parse(res_str.as_bytes())?.headers;
+
+    assert_eq!(
+        headers.get("LOCATION"),
+        Some(&String::from("/Login?Next=%2FHome"))
+    );
+    assert_eq!(headers.get("etag"), Some(&String::from("\"xYz\"")));
+    assert_eq!(
+        headers.get_all("Set-Cookie").collect::<Vec<_>>(),
+        vec!["a=Secret", "b=Token"]
+    );
+
+    headers.insert("SET-COOKIE", "c=1");
+    headers.append("X-Trace", "1");
+    assert!(headers.remove("etag"));
+
+    let (buf, _) = gen(headers.serialize(), Vec::new())?;
+
+    assert_eq!(
+        from_utf8(&buf)?,
+        "Location: /Login?Next=%2FHome\r\nSet-Cookie: c=1\r\nX-Trace: 1\r\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_request_from_url() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let req = Request::from_url("https://example.com:8443/search?q=rust#results")?;
+
+    assert_eq!(req.protocol, Protocol::HTTPS);
+    assert_eq!(req.host.as_deref(), Some("example.com"));
+    assert_eq!(req.port, Some(8443));
+    assert_eq!(req.path, "/search?q=rust");
+    assert_eq!(
+        req.headers.get("host"),
+        Some(&String::from("example.com:8443"))
+    );
+
+    let req = Request::from_url("http://[::1]")?;
+
+    assert_eq!(req.protocol, Protocol::HTTP);
+    assert_eq!(req.host.as_deref(), Some("[::1]"));
+    assert_eq!(req.port, Some(80));
+    assert_eq!(req.path, "/");
+
+    assert!(matches!(
+        Request::from_url("gopher://example.com"),
+        Err(Error::UnsupportedScheme(_))
+    ));
+
+    Ok(())
+}
+
+/**
+ * Serve each of *responses* to a single connection on a local port.
+ * Returns the port, and a channel receiving each request as it was read.
+ */
+#[cfg(test)]
+fn serve(responses: Vec<String>) -> (u16, std::sync::mpsc::Receiver<String>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        for response in responses {
+            let (mut socket, _) = listener.accept().unwrap();
+            let _ = tx.send(read_request(&mut socket));
+            socket.write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    (port, rx)
+}
+
+/**
+ * Read a request with an optional *CONTENT-LENGTH* body from *socket*, as a test server would.
+ */
+#[cfg(test)]
+fn read_request<R: Read>(socket: &mut R) -> String {
+    let mut req = Vec::new();
+    let mut buf = [0; 4096];
+
+    loop {
+        let head = req.windows(4).position(|w| w == b"\r\n\r\n");
+        let len = head.map(|head| {
+            let head_str = String::from_utf8_lossy(&req[..head]).to_ascii_lowercase();
+            let len = head_str
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length: "))
+                .map_or(0, |len| len.trim().parse().unwrap());
+            head + 4 + len
+        });
+
+        if len.is_some_and(|len| req.len() >= len) {
+            break;
+        }
+
+        match socket.read(&mut buf).unwrap() {
+            0 => break,
+            n => req.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    String::from_utf8_lossy(&req).into_owned()
+}
+
+#[test]
+fn test_follow_redirects() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let (other_port, other_rx) = serve(vec![
+        "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ndone".to_string()
+    ]);
+    let (port, rx) = serve(vec![
+        "HTTP/1.1 302 Found\r\nLocation: /b\r\nContent-Length: 0\r\n\r\n".to_string(),
+        "HTTP/1.1 307 Temporary Redirect\r\nLocation: c\r\nContent-Length: 0\r\n\r\n".to_string(),
+        format!(
+            "HTTP/1.1 308 Permanent Redirect\r\nLocation: http://127.0.0.1:{}/d\r\nContent-Length: 0\r\n\r\n",
+            other_port
+        ),
+    ]);
+    let mut req = Request::default();
+
+    req.method(Method::POST)
+        .path("/a")
+        .host("127.0.0.1")
+        .port(port)
+        .header("AUTHORIZATION", "Basic dXNlcjpwYXNz")
+        .header("PROXY-AUTHORIZATION", "Basic cHJveHk6cGFzcw==")
+        .header("COOKIE", "sid=abc")
+        .body(b"payload")
+        .redirect(RedirectPolicy::default());
+
+    let res = req.send()?;
+
+    assert_eq!(res.status.status_code, StatusCode::Success);
+    assert_eq!(
+        res.redirects,
+        vec![
+            format!("http://127.0.0.1:{}/b", port),
+            format!("http://127.0.0.1:{}/c", port),
+            format!("http://127.0.0.1:{}/d", other_port),
+        ]
+    );
+
+    assert!(rx.recv()?.starts_with("POST /a HTTP/1.1\r\n"));
+
+    let second = rx.recv()?;
+    assert!(second.starts_with("GET /b HTTP/1.1\r\n"));
+    assert!(!second.contains("payload") && !second.contains("CONTENT-LENGTH"));
+    assert!(second.contains("\r\nAUTHORIZATION") && second.contains("COOKIE"));
+
+    assert!(rx.recv()?.starts_with("GET /c HTTP/1.1\r\n"));
+
+    let last = other_rx.recv()?;
+    assert!(last.starts_with("GET /d HTTP/1.1\r\n"));
+    assert!(last.contains(&format!("HOST: 127.0.0.1:{}\r\n", other_port)));
+    assert!(!last.contains("AUTHORIZATION") && !last.contains("COOKIE"));
+
+    let (port, _rx) = serve(vec![
+        "HTTP/1.1 301 Moved Permanently\r\nLocation: http://example.com/\r\nContent-Length: 0\r\n\r\n".to_string(),
+        "HTTP/1.1 301 Moved Permanently\r\nLocation: /\r\nContent-Length: 0\r\n\r\n".to_string(),
+        "HTTP/1.1 301 Moved Permanently\r\nLocation: /\r\nContent-Length: 0\r\n\r\n".to_string(),
+    ]);
+    let mut req = Request::default();
+
+    req.host("127.0.0.1").port(port).redirect(RedirectPolicy {
+        max_hops: 1,
+        same_origin_only: true,
+        ..Default::default()
+    });
+
+    let res = req.send()?;
+    assert_eq!(res.status.status_code, StatusCode::MovedPermanently);
+    assert!(res.redirects.is_empty());
+
+    assert!(matches!(req.send(), Err(Error::TooManyRedirects(1))));
+
+    let (port, rx) = serve(vec![
+        "HTTP/1.1 302 Found\r\nLocation: ../x\r\nContent-Length: 0\r\n\r\n".to_string(),
+        "HTTP/1.1 302 Found\r\nLocation: ./y/.\r\nContent-Length: 0\r\n\r\n".to_string(),
+        "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n".to_string(),
+    ]);
+    let mut req = Request::default();
+
+    req.path("/a/b")
+        .host("127.0.0.1")
+        .port(port)
+        .redirect(RedirectPolicy::default());
+
+    let res = req.send()?;
+
+    assert_eq!(
+        res.redirects,
+        vec![
+            format!("http://127.0.0.1:{}/x", port),
+            format!("http://127.0.0.1:{}/y/", port),
+        ]
+    );
+    assert!(rx.recv()?.starts_with("GET /a/b HTTP/1.1\r\n"));
+    assert!(rx.recv()?.starts_with("GET /x HTTP/1.1\r\n"));
+    assert!(rx.recv()?.starts_with("GET /y/ HTTP/1.1\r\n"));
+
+    // A long redirect body is not read to reuse its connection, which is closed instead.
+    let memory = MemoryConnector::new();
+    let client = Client::new();
+    let mut req = Request::from_url("http://example.com/a")?;
+
+    memory
+        .respond(format!(
+            "HTTP/1.1 302 Found\r\nLocation: /b\r\nContent-Length: {}\r\n\r\n{}",
+            1 << 20,
+            "x".repeat(1 << 20)
+        ))
+        .respond("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+    req.connector(Arc::new(memory.clone()))
+        .keep_alive()
+        .redirect(RedirectPolicy::default());
+
+    assert_eq!(client.send(&req)?.redirects.len(), 1);
+    assert!(from_utf8(&memory.requests()[0])?.starts_with("GET /a HTTP/1.1\r\n"));
+    assert!(!from_utf8(&memory.requests()[0])?.contains("GET /b"));
+    assert!(from_utf8(&memory.requests()[1])?.starts_with("GET /b HTTP/1.1\r\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_timeouts() -> Result<(), Box<dyn StdError>> {
+    better_panic::install();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+
+    // Accepts connections but never responds.
+    std::thread::spawn(move || {
+        let sockets: Vec<_> = listener.incoming().take(2).collect();
+        std::thread::sleep(Duration::from_secs(5));
+        drop(sockets);
+    });
+
+    let mut req = Request::default();
+
+    req.host("127.0.0.1")
+        .port(port)
+        .read_timeout(Duration::from_millis(100));
+
+    assert!(matches!(req.send(), Err(Error::Timeout(Timeout::Read))));
+
+    req.read_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_millis(100));
+
+    assert!(matches!(req.send(), Err(Error::Timeout(Timeout::Total))));
+
+    Ok(())
+}
@@ -0,0 +1,358 @@
+use {
+    crate::{
+        parse::{params, Input, ParseResult},
+        random_hex, serialize_crlf,
+        CommonHeaders::*,
+        Headers,
+    },
+    cookie_factory::{combinator::slice, combinator::string, gen, sequence::tuple, SerializeFn},
+    nom::{
+        bytes::streaming::{tag, take_until, take_while},
+        character::streaming::crlf,
+        error::context,
+        sequence::terminated,
+    },
+    std::io,
+};
+
+/**
+ * A *multipart/form-data* body, made up of parts separated by a boundary.
+ * Parts are added with *text*, *file* and *part*, and the body is kept serialized as they are.
+ */
+#[derive(Debug, Clone)]
+pub struct MultiPartBody {
+    boundary: String,
+    parts: Vec<Part>,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Default for MultiPartBody {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MultiPartBody {
+    /**
+     * Create an empty body with a randomly generated boundary.
+     */
+    pub fn new() -> Self {
+        Self::with_boundary(&generate_boundary())
+    }
+
+    /**
+     * Create an empty body with the given *boundary*, which must not occur in any of its parts.
+     */
+    pub fn with_boundary(boundary: &str) -> Self {
+        let mut body = Self {
+            boundary: boundary.to_string(),
+            parts: Vec::new(),
+            data: Vec::new(),
+        };
+
+        body.data = body.serialize_close_delimiter();
+        body
+    }
+
+    /**
+     * Add a text field.
+     */
+    pub fn text(&mut self, name: &str, value: &str) -> &mut Self {
+        self.part(Part::text(name, value))
+    }
+
+    /**
+     * Add a file, sent with its *filename* and *content_type*.
+     */
+    pub fn file(
+        &mut self,
+        name: &str,
+        filename: &str,
+        content_type: &str,
+        data: impl Into<Vec<u8>>,
+    ) -> &mut Self {
+        self.part(Part::file(name, filename, content_type, data))
+    }
+
+    /**
+     * Add a part, replacing the close delimiter at the end of the body.
+     */
+    pub fn part(&mut self, part: Part) -> &mut Self {
+        let close = self.serialize_close_delimiter();
+        let part_data = self.serialize_part(&part);
+
+        self.data.truncate(self.data.len() - close.len());
+        self.data.extend_from_slice(&part_data);
+        self.data.extend_from_slice(&close);
+        self.parts.push(part);
+        self
+    }
+
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    /**
+     * The *CONTENT-TYPE* to send this body with.
+     */
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary=\"{}\"", self.boundary)
+    }
+
+    fn serialize_part(&self, part: &Part) -> Vec<u8> {
+        let serializer = tuple((
+            string("--"),
+            string(&self.boundary),
+            serialize_crlf(),
+            part.serialize(),
+            serialize_crlf(),
+        ));
+
+        gen(serializer, Vec::new())
+            .map(|(data, _)| data)
+            .unwrap_or_default()
+    }
+
+    fn serialize_close_delimiter(&self) -> Vec<u8> {
+        format!("--{}--\r\n", self.boundary).into_bytes()
+    }
+
+    /**
+     * The boundary of a multipart body, from the *CONTENT-TYPE* in *headers*.
+     */
+    pub fn boundary_of(headers: &Headers) -> Option<String> {
+        let content_type = headers.get(&ContentType.to_string())?;
+
+        match content_type.trim_start().get(..10) {
+            Some(media) if media.eq_ignore_ascii_case("multipart/") => {
+                header_param(content_type, "boundary").filter(|boundary| !boundary.is_empty())
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * Parse a multipart body with the given *boundary* into its parts.
+     * The preamble before the first part and the epilogue after the last are ignored.
+     */
+    pub fn parse<'a>(i: Input<'a>, boundary: &str) -> ParseResult<'a, Self> {
+        let delimiter = format!("--{}", boundary);
+        let next_delimiter = format!("\r\n{}", delimiter);
+
+        context("Multipart Body", move |start: Input<'a>| {
+            let (mut i, _preamble) = take_until(delimiter.as_str())(start)?;
+            let mut parts = Vec::new();
+
+            loop {
+                let (rest, _) = tag(delimiter.as_str())(i)?;
+
+                if let Ok((_, _)) = tag::<_, _, crate::parse::Error<Input>>("--")(rest) {
+                    break;
+                }
+
+                let (rest, _padding) =
+                    terminated(take_while(|c| c == b' ' || c == b'\t'), crlf)(rest)?;
+                let (rest, part) = Part::parse(rest, &next_delimiter)?;
+
+                // Leave the delimiter for the next iteration, without the line break that precedes it.
+                i = &rest[2..];
+                parts.push(part);
+            }
+
+            let res = Self {
+                boundary: boundary.to_string(),
+                parts,
+                data: start.to_vec(),
+            };
+
+            Ok((&start[start.len()..], res))
+        })(i)
+    }
+}
+
+/**
+ * A single part of a multipart body, with its own headers.
+ */
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub headers: Headers,
+    pub data: Vec<u8>,
+}
+
+impl Part {
+    /**
+     * A form field named *name* with a text *value*.
+     */
+    pub fn text(name: &str, value: &str) -> Self {
+        let mut headers = Headers::new();
+
+        headers.insert(
+            &ContentDisposition.to_string(),
+            &format!("form-data; name=\"{}\"", escape(name)),
+        );
+        Self {
+            headers,
+            data: value.as_bytes().to_vec(),
+        }
+    }
+
+    /**
+     * A form field named *name* holding a file's *data*, with its *filename* and *content_type*.
+     */
+    pub fn file(name: &str, filename: &str, content_type: &str, data: impl Into<Vec<u8>>) -> Self {
+        let mut headers = Headers::new();
+
+        headers.insert(
+            &ContentDisposition.to_string(),
+            &format!(
+                "form-data; name=\"{}\"; filename=\"{}\"",
+                escape(name),
+                escape(filename)
+            ),
+        );
+        headers.insert(&ContentType.to_string(), content_type);
+        Self {
+            headers,
+            data: data.into(),
+        }
+    }
+
+    /**
+     * The name of the form field, from *CONTENT-DISPOSITION*.
+     */
+    pub fn name(&self) -> Option<String> {
+        self.disposition_param("name")
+    }
+
+    /**
+     * The name of the file this part was read from, if it is a file.
+     */
+    pub fn filename(&self) -> Option<String> {
+        self.disposition_param("filename")
+    }
+
+    pub fn content_type(&self) -> Option<&String> {
+        self.headers.get(&ContentType.to_string())
+    }
+
+    fn disposition_param(&self, name: &str) -> Option<String> {
+        header_param(self.headers.get(&ContentDisposition.to_string())?, name)
+    }
+
+    fn serialize<'a, W: io::Write + 'a>(&'a self) -> impl SerializeFn<W> + 'a {
+        tuple((
+            self.headers.serialize(),
+            serialize_crlf(),
+            slice(&self.data),
+        ))
+    }
+
+    /**
+     * Parse a part's headers and data, up to the *delimiter* that ends it.
+     */
+    fn parse<'a>(i: Input<'a>, delimiter: &str) -> ParseResult<'a, Self> {
+        context("Part", |i| {
+            let (i, headers) = Headers::parse(i)?;
+            let (i, data) = take_until(delimiter)(i)?;
+
+            Ok((
+                i,
+                Self {
+                    headers,
+                    data: data.to_vec(),
+                },
+            ))
+        })(i)
+    }
+}
+
+/**
+ * Generate a boundary that is very unlikely to occur in any part.
+ */
+fn generate_boundary() -> String {
+    format!("------------------------{}", random_hex())
+}
+
+/**
+ * Escape a name for a quoted *CONTENT-DISPOSITION* parameter, the way browsers do.
+ */
+fn escape(name: &str) -> String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/**
+ * The value of the parameter *name* in a header value such as *form-data; name="field"*.
+ */
+pub(crate) fn header_param(value: &str, name: &str) -> Option<String> {
+    let (_, rest) = value.split_once(';')?;
+
+    params(rest, ';')
+        .into_iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+#[test]
+fn test_multipart_body() -> Result<(), crate::Error> {
+    use crate::{Body, Method, Request};
+
+    better_panic::install();
+
+    let mut form = MultiPartBody::new();
+    let mut buf = Vec::new();
+
+    form.text("title", "Holiday \"photos\"").file(
+        "photo",
+        "beach; 1.png",
+        "image/png",
+        &b"\x89PNG\r\n--"[..],
+    );
+    Request::default()
+        .method(Method::POST)
+        .multipart(form.clone())
+        .write_to(&mut buf)?;
+
+    let req = Request::parse(&buf)?;
+    let parsed = match req.body {
+        Some(Body::Multi(body)) => body,
+        body => panic!("Expected a multipart body, got {:?}", body),
+    };
+
+    assert_eq!(parsed.boundary(), form.boundary());
+    assert_eq!(parsed.parts().len(), 2);
+    assert_eq!(parsed.parts()[0].name(), Some("title".to_string()));
+    assert_eq!(parsed.parts()[0].data, b"Holiday \"photos\"");
+    assert_eq!(parsed.parts()[1].name(), Some("photo".to_string()));
+    assert_eq!(
+        parsed.parts()[1].filename(),
+        Some("beach; 1.png".to_string())
+    );
+    assert_eq!(
+        parsed.parts()[1].content_type(),
+        Some(&"image/png".to_string())
+    );
+    assert_eq!(parsed.parts()[1].data, b"\x89PNG\r\n--");
+
+    let body = b"preamble\r\n--xyz\r\nContent-Disposition: form-data; name=a\r\n\r\n1\r\n\
+        --xyz  \r\n\r\n\r\n--xyz--\r\nepilogue";
+    let (rest, parsed) = MultiPartBody::parse(body, "xyz").unwrap();
+
+    assert!(rest.is_empty());
+    assert_eq!(parsed.parts().len(), 2);
+    assert_eq!(parsed.parts()[0].name(), Some("a".to_string()));
+    assert_eq!(parsed.parts()[0].data, b"1");
+    assert!(parsed.parts()[1]
+        .headers
+        .get("Content-Disposition")
+        .is_none());
+    assert!(parsed.parts()[1].data.is_empty());
+    assert!(MultiPartBody::parse(b"--xyz\r\n\r\nno close delimiter", "xyz").is_err());
+
+    Ok(())
+}
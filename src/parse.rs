@@ -50,9 +50,9 @@ impl<I> Error<I> {
     }
 }
 
-impl<'a> fmt::Debug for Error<&'a [u8]> {
+impl fmt::Debug for Error<&[u8]> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "/!\\ Parsing error\n")?;
+        writeln!(f, "/!\\ Parsing error")?;
 
         let mut shown_input = None;
         let margin_left = 4;
@@ -79,7 +79,7 @@ impl<'a> fmt::Debug for Error<&'a [u8]> {
                 for b in s {
                     write!(f, "{:02X} ", b)?;
                 }
-                write!(f, "\n")?;
+                writeln!(f)?;
 
                 write!(f, "{}", margin_str)?;
                 for i in 0..s.len() {
@@ -91,7 +91,7 @@ impl<'a> fmt::Debug for Error<&'a [u8]> {
                         write!(f, "   ")?;
                     };
                 }
-                write!(f, "\n")?;
+                writeln!(f)?;
 
                 Ok(())
             };
@@ -101,10 +101,10 @@ impl<'a> fmt::Debug for Error<&'a [u8]> {
                 ErrorKind::Context(ctx) => format!("...in {}", ctx),
                 ErrorKind::Nom(err) => format!("nom error {:?}", err),
                 ErrorKind::Custom(err) => format!("err: {}", err),
-                ErrorKind::Malformed => format!("Malformed packet"),
+                ErrorKind::Malformed => "Malformed packet".to_string(),
             };
 
-            write!(f, "{}\n", prefix)?;
+            writeln!(f, "{}", prefix)?;
             match shown_input {
                 None => {
                     shown_input.replace(input);
@@ -137,3 +137,72 @@ impl<I> NomParseError<I> for Error<I> {
         other
     }
 }
+
+/**
+ * Decode a parsed token as UTF-8, failing the parse instead of panicking on invalid input.
+ */
+pub fn as_str(i: Input<'_>) -> Result<&str, nom::Err<Error<Input<'_>>>> {
+    std::str::from_utf8(i)
+        .map_err(|e| nom::Err::Failure(Error::custom(i, format!("[UTF-8 decoding] {}", e))))
+}
+
+/**
+ * Split a list of parameters such as *a=1; b="two"* on *separator* into names and values.
+ * Quoted values are unquoted, and a parameter without a value has an empty one.
+ */
+pub fn params(mut s: &str, separator: char) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+
+    loop {
+        s = s.trim_start_matches(|c: char| c == separator || c.is_whitespace());
+        if s.is_empty() {
+            return params;
+        }
+
+        let end = s.find(['=', separator]).unwrap_or(s.len());
+        let key = s[..end].trim().to_string();
+        let value = match s[end..].strip_prefix('=').map(str::trim_start) {
+            Some(rest) => match rest.strip_prefix('"') {
+                Some(quoted) => {
+                    let mut value = String::new();
+                    let mut end = quoted.len();
+                    let mut chars = quoted.char_indices();
+
+                    while let Some((idx, c)) = chars.next() {
+                        match c {
+                            '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                            '"' => {
+                                end = idx + 1;
+                                break;
+                            }
+                            c => value.push(c),
+                        }
+                    }
+
+                    // Skip anything between the closing quote and the next separator.
+                    let rest = &quoted[end..];
+                    s = &rest[rest.find(separator).unwrap_or(rest.len())..];
+                    value
+                }
+                None => {
+                    let end = rest.find(separator).unwrap_or(rest.len());
+                    s = &rest[end..];
+                    rest[..end].trim().to_string()
+                }
+            },
+            None => {
+                s = &s[end..];
+                String::new()
+            }
+        };
+
+        params.push((key, value));
+    }
+}
+
+/**
+ * Whether a byte may appear in a token, as defined by RFC 7230 section 3.2.6.
+ */
+pub fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
@@ -0,0 +1,185 @@
+use {
+    crate::{
+        CommonHeaders::*, Error, Headers, Method, Protocol, Request, StatusCode, StreamingResponse,
+        Url,
+    },
+    std::{borrow::Cow, io::Read},
+};
+
+/**
+ * Controls which redirects *Request::send* follows. Redirects are only followed when a policy is set.
+ */
+#[derive(Debug, Clone)]
+pub struct RedirectPolicy {
+    // The maximum number of redirects to follow before failing with Error::TooManyRedirects.
+    pub max_hops: usize,
+    // Only follow redirects to the same scheme, host and port.
+    pub same_origin_only: bool,
+    // Follow redirects from HTTPS to plain HTTP.
+    pub allow_downgrade: bool,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max_hops: 10,
+            same_origin_only: false,
+            allow_downgrade: false,
+        }
+    }
+}
+
+impl<'a> Request<'a> {
+    /**
+     * Build the request that follows a response with *status* and *headers*, along with its absolute URL.
+     * Returns None if the response is not a redirect, or if the policy forbids following it.
+     */
+    pub(crate) fn follow(
+        &self,
+        status: StatusCode,
+        headers: &Headers,
+        policy: &RedirectPolicy,
+    ) -> Result<Option<(String, Request<'a>)>, Error> {
+        let (method, keep_body) = match status {
+            StatusCode::SeeOther if self.method == Method::HEAD => (Method::HEAD, false),
+            StatusCode::SeeOther => (Method::GET, false),
+            // Historically, user agents change POST to GET for these, and servers rely on it.
+            StatusCode::MovedPermanently | StatusCode::Found if self.method == Method::POST => {
+                (Method::GET, false)
+            }
+            StatusCode::MovedPermanently
+            | StatusCode::Found
+            | StatusCode::TemporaryRedirect
+            | StatusCode::PermanentRedirect => (self.method.clone(), true),
+            _ => return Ok(None),
+        };
+
+        // A streamed body has already been sent, and cannot be sent again.
+        if keep_body && self.body_reader.is_some() {
+            return Ok(None);
+        }
+
+        let (base, location) = match (self.url(), headers.get(&Location.to_string())) {
+            (Some(base), Some(location)) => {
+                let location = base.join(location);
+                (base, location)
+            }
+            _ => return Ok(None),
+        };
+        let url = Url::parse(&location)?;
+        let same_origin = url.protocol == base.protocol
+            && url.host.eq_ignore_ascii_case(base.host)
+            && url.port_or_default() == base.port_or_default();
+        let downgrade = base.protocol == Protocol::HTTPS && url.protocol == Protocol::HTTP;
+
+        if (policy.same_origin_only && !same_origin) || (downgrade && !policy.allow_downgrade) {
+            return Ok(None);
+        }
+
+        let mut headers = self.headers.clone();
+
+        headers.insert(&Host.to_string(), &url.authority());
+
+        // Credentials meant for one origin must not leak to another.
+        if !same_origin {
+            for header in &[Authorization, ProxyAuthorization, Cookie] {
+                headers.remove(&header.to_string());
+            }
+        }
+        if !keep_body {
+            for header in &[
+                ContentLength,
+                ContentType,
+                ContentEncoding,
+                TransferEncoding,
+            ] {
+                headers.remove(&header.to_string());
+            }
+        }
+
+        let next = Request {
+            method,
+            protocol: url.protocol,
+            host: Some(Cow::Owned(url.host.to_string())),
+            path: Cow::Owned(url.target().into_owned()),
+            port: Some(url.port_or_default()),
+            version: self.version,
+            headers,
+            body: if keep_body { self.body.clone() } else { None },
+            body_reader: None,
+            trailers: if keep_body {
+                self.trailers.clone()
+            } else {
+                Default::default()
+            },
+            redirect: self.redirect.clone(),
+            timeouts: self.timeouts,
+            decompress: self.decompress,
+            decompression_limits: self.decompression_limits,
+            digest: if same_origin {
+                self.digest.clone()
+            } else {
+                None
+            },
+            proxy: self.proxy.clone(),
+            connector: self.connector.clone(),
+        };
+
+        Ok(Some((location, next)))
+    }
+
+    /**
+     * The URL this request is sent to, if it has a host.
+     */
+    pub(crate) fn url(&self) -> Option<Url<'_>> {
+        let (path, query) = match self.path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (self.path.as_ref(), None),
+        };
+
+        self.host.as_ref().map(|host| Url {
+            protocol: self.protocol,
+            username: None,
+            password: None,
+            host,
+            port: self.port,
+            path,
+            query,
+            fragment: None,
+        })
+    }
+}
+
+impl<'c, R: Read> StreamingResponse<'c, R> {
+    /**
+     * Follow redirects for *req* according to *policy*, starting from its first response.
+     * Each redirected request is sent with *send*, after the body of the redirect has been discarded.
+     * A long redirect body is not read at all: its connection is closed instead of being reused.
+     */
+    pub(crate) fn follow_redirects<F>(
+        mut self,
+        req: &Request,
+        policy: &RedirectPolicy,
+        send: F,
+    ) -> Result<Self, Error>
+    where
+        F: Fn(&Request) -> Result<Self, Error>,
+    {
+        let mut redirects = Vec::new();
+        let mut next = req.follow(self.status.status_code, &self.headers, policy)?;
+
+        while let Some((url, req)) = next {
+            if redirects.len() == policy.max_hops {
+                return Err(Error::TooManyRedirects(policy.max_hops));
+            }
+
+            self.drain();
+            self = send(&req)?;
+            redirects.push(url);
+            next = req.follow(self.status.status_code, &self.headers, policy)?;
+        }
+
+        self.redirects = redirects;
+        Ok(self)
+    }
+}
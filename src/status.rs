@@ -0,0 +1,202 @@
+use {
+    crate::parse::{as_str, Input, ParseResult},
+    nom::{
+        bytes::streaming::take_while_m_n,
+        character::{is_digit, streaming::space1},
+        error::context,
+        sequence::terminated,
+    },
+    std::fmt::{self, Display},
+};
+
+/**
+ * Declares the registered status codes along with their numeric codes and canonical reason phrases.
+ */
+macro_rules! status_codes {
+    ($($name:ident = $code:expr, $reason:expr;)+) => {
+        /**
+         * Response status codes, as registered with IANA.
+         * Unregistered codes are carried as-is by *Other*.
+         */
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum StatusCode {
+            $($name,)+
+            Other(u16),
+        }
+
+        impl StatusCode {
+            /**
+             * Look up the status code for a three-digit numeric code.
+             */
+            pub fn from_code(code: u16) -> Self {
+                match code {
+                    $($code => StatusCode::$name,)+
+                    code => StatusCode::Other(code),
+                }
+            }
+
+            /**
+             * The numeric code of this status.
+             */
+            pub fn code(&self) -> u16 {
+                match self {
+                    $(StatusCode::$name => $code,)+
+                    StatusCode::Other(code) => *code,
+                }
+            }
+
+            /**
+             * The canonical reason phrase of this status, if it is registered.
+             */
+            pub fn reason(&self) -> Option<&'static str> {
+                match self {
+                    $(StatusCode::$name => Some($reason),)+
+                    StatusCode::Other(_) => None,
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    Continue = 100, "Continue";
+    SwitchingProtocols = 101, "Switching Protocols";
+    Processing = 102, "Processing";
+    EarlyHints = 103, "Early Hints";
+
+    Success = 200, "OK";
+    Created = 201, "Created";
+    Accepted = 202, "Accepted";
+    NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+    NoContent = 204, "No Content";
+    ResetContent = 205, "Reset Content";
+    PartialContent = 206, "Partial Content";
+    MultiStatus = 207, "Multi-Status";
+    AlreadyReported = 208, "Already Reported";
+    ImUsed = 226, "IM Used";
+
+    MultipleChoices = 300, "Multiple Choices";
+    MovedPermanently = 301, "Moved Permanently";
+    Found = 302, "Found";
+    SeeOther = 303, "See Other";
+    NotModified = 304, "Not Modified";
+    UseProxy = 305, "Use Proxy";
+    TemporaryRedirect = 307, "Temporary Redirect";
+    PermanentRedirect = 308, "Permanent Redirect";
+
+    BadRequest = 400, "Bad Request";
+    Unauthorized = 401, "Unauthorized";
+    PaymentRequired = 402, "Payment Required";
+    Forbidden = 403, "Forbidden";
+    NotFound = 404, "Not Found";
+    MethodNotAllowed = 405, "Method Not Allowed";
+    NotAcceptable = 406, "Not Acceptable";
+    ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+    RequestTimeout = 408, "Request Timeout";
+    Conflict = 409, "Conflict";
+    Gone = 410, "Gone";
+    LengthRequired = 411, "Length Required";
+    PreconditionFailed = 412, "Precondition Failed";
+    ContentTooLarge = 413, "Content Too Large";
+    UriTooLong = 414, "URI Too Long";
+    UnsupportedMediaType = 415, "Unsupported Media Type";
+    RangeNotSatisfiable = 416, "Range Not Satisfiable";
+    ExpectationFailed = 417, "Expectation Failed";
+    MisdirectedRequest = 421, "Misdirected Request";
+    UnprocessableContent = 422, "Unprocessable Content";
+    Locked = 423, "Locked";
+    FailedDependency = 424, "Failed Dependency";
+    TooEarly = 425, "Too Early";
+    UpgradeRequired = 426, "Upgrade Required";
+    PreconditionRequired = 428, "Precondition Required";
+    TooManyRequests = 429, "Too Many Requests";
+    RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+    UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+
+    InternalServerError = 500, "Internal Server Error";
+    NotImplemented = 501, "Not Implemented";
+    BadGateway = 502, "Bad Gateway";
+    ServiceUnavailable = 503, "Service Unavailable";
+    GatewayTimeout = 504, "Gateway Timeout";
+    HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+    VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+    InsufficientStorage = 507, "Insufficient Storage";
+    LoopDetected = 508, "Loop Detected";
+    NotExtended = 510, "Not Extended";
+    NetworkAuthenticationRequired = 511, "Network Authentication Required";
+}
+
+impl StatusCode {
+    /**
+     * 1xx: the request was received and processing continues.
+     */
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.code())
+    }
+
+    /**
+     * 2xx: the request was successfully received, understood and accepted.
+     */
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code())
+    }
+
+    /**
+     * 3xx: further action is needed to complete the request.
+     */
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.code())
+    }
+
+    /**
+     * 4xx: the request contains bad syntax or cannot be fulfilled.
+     */
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.code())
+    }
+
+    /**
+     * 5xx: the server failed to fulfill an apparently valid request.
+     */
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.code())
+    }
+
+    pub fn parse(i: Input) -> ParseResult<Self> {
+        context("Status Code", |i| {
+            let (i, status_code) = terminated(take_while_m_n(3, 3, is_digit), space1)(i)?;
+            let code = as_str(status_code)?.parse().unwrap_or_default();
+
+            Ok((i, Self::from_code(code)))
+        })(i)
+    }
+}
+
+impl Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason() {
+            Some(reason) => write!(f, "{} {}", self.code(), reason),
+            None => write!(f, "{}", self.code()),
+        }
+    }
+}
+
+#[test]
+fn test_status_codes() {
+    assert_eq!(StatusCode::from_code(204), StatusCode::NoContent);
+    assert_eq!(StatusCode::from_code(599), StatusCode::Other(599));
+    assert_eq!(StatusCode::TooManyRequests.code(), 429);
+    assert_eq!(StatusCode::Found.reason(), Some("Found"));
+    assert_eq!(
+        StatusCode::ServiceUnavailable.to_string(),
+        "503 Service Unavailable"
+    );
+    assert!(StatusCode::Found.is_redirect());
+    assert!(StatusCode::Other(299).is_success());
+    assert!(StatusCode::TooManyRequests.is_client_error());
+    assert!(StatusCode::from_code(503).is_server_error());
+
+    let (_, status) = StatusCode::parse(b"299 Custom").unwrap();
+    assert_eq!(status, StatusCode::Other(299));
+    assert!(StatusCode::parse(b"2000 Bad").is_err());
+}
@@ -0,0 +1,245 @@
+use {
+    crate::{
+        connection::Connection,
+        decoder::{Decoder, DecompressionLimits},
+        framing::{BodyLength, MessageReader, RawBody},
+        Body,
+        CommonHeaders::*,
+        Error, Headers, Method, Request, Response, ResponseStatus, SinglePartBody, StatusCode,
+    },
+    std::{
+        io::{self, Read},
+        mem,
+    },
+};
+
+/**
+ * A response whose body has not been read yet. The body is read from the connection on demand through
+ * its Read implementation, with its transfer-coding and content-codings removed.
+ */
+pub struct StreamingResponse<'c, R: Read = Connection> {
+    pub status: ResponseStatus,
+    pub headers: Headers,
+    // The URLs of the redirects that were followed to get this response, in order.
+    pub redirects: Vec<String>,
+    body: Decoder<RawBody<'c, R>>,
+    limits: DecompressionLimits,
+    // The number of bytes the body has decoded to so far.
+    decoded: u64,
+}
+
+impl<'c, R: Read> StreamingResponse<'c, R> {
+    /**
+     * Read the status and headers of a response from *stream*, leaving the body to be read on demand.
+     * *method* is the method of the request this is a response to, since it decides whether there is a body.
+     */
+    pub fn read_from(stream: R, method: &Method) -> Result<Self, Error> {
+        Self::read_head(stream, method)?.decode(DecompressionLimits::default())
+    }
+
+    /**
+     * Like *read_from*, but without removing content-codings from the body.
+     * Interim (1xx) responses are skipped, except for *101 Switching Protocols*.
+     */
+    pub(crate) fn read_head(stream: R, method: &Method) -> Result<Self, Error> {
+        let mut reader = MessageReader::new(stream);
+
+        loop {
+            let status = reader.parse(ResponseStatus::parse).map_err(|e| match e {
+                Error::Incomplete if reader.received == 0 => Error::ConnectionClosed,
+                e => e,
+            })?;
+            let headers = reader.parse(Headers::parse)?;
+            let code = status.status_code;
+
+            if code.is_informational() && code != StatusCode::SwitchingProtocols {
+                continue;
+            }
+
+            let len = BodyLength::of_response(code, &headers, method)?;
+
+            return Ok(Self {
+                status,
+                headers,
+                redirects: Vec::new(),
+                body: Decoder::Identity(RawBody::new(reader, len)),
+                limits: DecompressionLimits::default(),
+                decoded: 0,
+            });
+        }
+    }
+
+    /**
+     * Remove the content-codings from the body as it is read.
+     * The *CONTENT-ENCODING* and *CONTENT-LENGTH* headers are removed, since they no longer apply.
+     * Reading fails with Error::DecompressionLimit once the decoded body exceeds *limits*.
+     */
+    pub(crate) fn decode(self, limits: DecompressionLimits) -> Result<Self, Error> {
+        let Self {
+            status,
+            mut headers,
+            redirects,
+            body,
+            decoded,
+            ..
+        } = self;
+
+        let body = match body {
+            Decoder::Identity(mut raw) if raw.has_body => match raw.at_end()? {
+                // An empty body has nothing to decode, whatever content-codings it is labelled with.
+                true => Decoder::Identity(raw),
+                false => Decoder::new(raw, &headers)?,
+            },
+            body => body,
+        };
+
+        if !body.is_identity() {
+            headers.remove(&ContentEncoding.to_string());
+            headers.remove(&ContentLength.to_string());
+        }
+
+        Ok(Self {
+            status,
+            headers,
+            redirects,
+            body,
+            limits,
+            decoded,
+        })
+    }
+
+    /**
+     * Remove the content-codings from the body if *req* asks for it.
+     */
+    pub(crate) fn decode_for(self, req: &Request) -> Result<Self, Error> {
+        match req.decompress {
+            true => self.decode(req.decompression_limits),
+            false => Ok(self),
+        }
+    }
+
+    /**
+     * Hand the stream to *f* once the body has been read to its end.
+     */
+    pub(crate) fn on_done(&mut self, f: impl FnOnce(R) + 'c) {
+        self.body.get_mut().on_done(f);
+    }
+
+    /**
+     * Read and discard the rest of the body.
+     */
+    pub(crate) fn drain(&mut self) {
+        self.body.get_mut().drain();
+    }
+
+    /**
+     * The trailers sent after a chunked body. Empty until the body has been read to its end.
+     */
+    pub fn trailers(&self) -> &Headers {
+        &self.body.get_ref().trailers
+    }
+
+    /**
+     * Read the rest of the body into memory.
+     */
+    pub fn collect(mut self) -> Result<Response, Error> {
+        let mut data = Vec::new();
+
+        self.read_to_end(&mut data).map_err(|e| {
+            // Errors from reading the connection are wrapped; anything else comes from decoding.
+            match e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+                true => Error::from(e),
+                false => Error::Decompression(e),
+            }
+        })?;
+
+        let raw = self.body.get_mut();
+        let body = match raw.has_body {
+            true => Some(Body::Single(SinglePartBody { data }).split_multipart(&self.headers)),
+            false => None,
+        };
+
+        Ok(Response {
+            status: self.status,
+            headers: self.headers,
+            body,
+            trailers: mem::take(&mut raw.trailers),
+            redirects: self.redirects,
+        })
+    }
+}
+
+impl<'c, R: Read> Read for StreamingResponse<'c, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.body.read(buf)?;
+
+        if !self.body.is_identity() {
+            self.decoded += len as u64;
+            self.limits
+                .check(self.body.get_ref().read, self.decoded)
+                .map_err(io::Error::from)?;
+        }
+
+        // Decoders stop at the end of their data, before the end of a chunked body and its trailers.
+        if len == 0 && !buf.is_empty() {
+            self.drain();
+        }
+        Ok(len)
+    }
+}
+
+impl Response {
+    /**
+     * Read a single response from *stream*, stopping at the end of the message as framed by its headers
+     * rather than waiting for the server to close the connection.
+     * *method* is the method of the request this is a response to, since it decides whether there is a body.
+     * Interim (1xx) responses are skipped, except for *101 Switching Protocols*.
+     */
+    pub fn read_from<R: Read>(stream: &mut R, method: &Method) -> Result<Self, Error> {
+        StreamingResponse::read_head(stream, method)?.collect()
+    }
+}
+
+#[cfg(feature = "chunks")]
+#[test]
+fn test_streaming_response() -> Result<(), Error> {
+    use {
+        flate2::{write::GzEncoder, Compression},
+        std::io::Write,
+    };
+
+    better_panic::install();
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&[b'a'; 100_000])?;
+    let gzipped = encoder.finish()?;
+
+    let mut stream =
+        b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+    for chunk in gzipped.chunks(1000) {
+        write!(stream, "{:x}\r\n", chunk.len())?;
+        stream.extend_from_slice(chunk);
+        stream.extend_from_slice(b"\r\n");
+    }
+    stream.extend_from_slice(b"0\r\nChecksum: abc\r\n\r\n");
+
+    let mut res = StreamingResponse::read_from(&stream[..], &Method::GET)?;
+    let mut buf = [0; 10];
+
+    res.read_exact(&mut buf)?;
+    assert_eq!(&buf, b"aaaaaaaaaa");
+    assert!(res.headers.get("content-encoding").is_none());
+
+    let res = res.collect()?;
+    assert_eq!(res.body.unwrap().len(), 100_000 - 10);
+    assert_eq!(res.trailers.get("checksum"), Some(&"abc".to_string()));
+
+    let stream: &[u8] =
+        b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 5\r\n\r\nhello";
+    assert!(matches!(
+        StreamingResponse::read_from(stream, &Method::GET)?.collect(),
+        Err(Error::Decompression(_))
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+
+/**
+ * Common suffixes under which anyone can register a name, used when no full list is loaded.
+ * Single-label suffixes (top-level domains) are always public, so they are not listed.
+ */
+const DEFAULT_RULES: &str = "
+ac.uk
+co.uk
+gov.uk
+ltd.uk
+me.uk
+net.uk
+org.uk
+plc.uk
+sch.uk
+com.au
+edu.au
+gov.au
+net.au
+org.au
+co.nz
+net.nz
+org.nz
+co.jp
+ne.jp
+or.jp
+co.kr
+co.in
+net.in
+org.in
+com.br
+net.br
+org.br
+com.cn
+net.cn
+org.cn
+com.mx
+com.tr
+co.za
+*.ck
+!www.ck
+github.io
+gitlab.io
+herokuapp.com
+netlify.app
+pages.dev
+vercel.app
+azurewebsites.net
+cloudfront.net
+appspot.com
+blogspot.com
+";
+
+/**
+ * A list of public suffixes, such as *com* or *co.uk*, under which cookies may not be set,
+ * in the format of the Public Suffix List (https://publicsuffix.org/list/).
+ * The default list only covers common suffixes; load the full list for complete protection.
+ */
+#[derive(Debug, Clone)]
+pub struct PublicSuffixList {
+    rules: HashSet<String>,
+    // Suffixes of the form *\*.parent*, stored as *parent*.
+    wildcards: HashSet<String>,
+    // Exceptions to wildcards, of the form *!name*, stored as *name*.
+    exceptions: HashSet<String>,
+}
+
+impl Default for PublicSuffixList {
+    fn default() -> Self {
+        Self::parse(DEFAULT_RULES)
+    }
+}
+
+impl PublicSuffixList {
+    /**
+     * Parse a list with one rule per line. Comment lines and blank lines are ignored,
+     * as is anything after the first whitespace on a line.
+     */
+    pub fn parse(list: &str) -> Self {
+        let mut suffixes = Self {
+            rules: HashSet::new(),
+            wildcards: HashSet::new(),
+            exceptions: HashSet::new(),
+        };
+
+        for line in list.lines() {
+            let rule = match line.split_whitespace().next() {
+                Some(rule) if !rule.starts_with("//") => rule.to_ascii_lowercase(),
+                _ => continue,
+            };
+
+            if let Some(exception) = rule.strip_prefix('!') {
+                suffixes.exceptions.insert(exception.to_string());
+            } else if let Some(parent) = rule.strip_prefix("*.") {
+                suffixes.wildcards.insert(parent.to_string());
+            } else {
+                suffixes.rules.insert(rule);
+            }
+        }
+
+        suffixes
+    }
+
+    /**
+     * Whether *domain* is a public suffix. Any single label is, following the implicit wildcard rule.
+     */
+    pub fn is_public(&self, domain: &str) -> bool {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+
+        if self.exceptions.contains(&domain) {
+            return false;
+        }
+
+        match domain.split_once('.') {
+            Some((_, parent)) => self.rules.contains(&domain) || self.wildcards.contains(parent),
+            None => true,
+        }
+    }
+}
+
+#[test]
+fn test_public_suffixes() {
+    let suffixes = PublicSuffixList::default();
+
+    assert!(suffixes.is_public("com"));
+    assert!(suffixes.is_public("co.uk"));
+    assert!(suffixes.is_public("Example.CK"));
+    assert!(suffixes.is_public("github.io"));
+    assert!(!suffixes.is_public("www.ck"));
+    assert!(!suffixes.is_public("example.com"));
+    assert!(!suffixes.is_public("example.co.uk"));
+
+    let suffixes = PublicSuffixList::parse("// comment\n\nexample.com extra\n");
+
+    assert!(suffixes.is_public("example.com"));
+    assert!(!suffixes.is_public("co.uk"));
+}
@@ -0,0 +1,96 @@
+use std::{
+    error::Error as StdError,
+    fmt::{self, Display},
+    io,
+    time::{Duration, Instant},
+};
+
+/**
+ * Limits on how long sending a request may block. Unset limits never time out.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    // The longest time to wait for a TCP connection to be established.
+    pub connect: Option<Duration>,
+    // The longest time a single read or write on the connection may block.
+    pub read: Option<Duration>,
+    // The longest time the whole exchange may take, including redirects.
+    pub total: Option<Duration>,
+}
+
+impl Timeouts {
+    /**
+     * Use the limits of *self*, falling back to those of *defaults* for any that are unset.
+     */
+    pub fn or(&self, defaults: &Timeouts) -> Timeouts {
+        Timeouts {
+            connect: self.connect.or(defaults.connect),
+            read: self.read.or(defaults.read),
+            total: self.total.or(defaults.total),
+        }
+    }
+
+    /**
+     * The instant by which an exchange started now must be complete.
+     */
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.total.map(|total| Instant::now() + total)
+    }
+}
+
+/**
+ * Which limit was exceeded when a request timed out.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    // The connection could not be established in time.
+    Connect,
+    // The server did not send or accept any data in time.
+    Read,
+    // The whole exchange took too long.
+    Total,
+}
+
+impl Timeout {
+    /**
+     * Wrap this timeout in an io::Error, so it can be returned from Read and Write implementations.
+     */
+    pub(crate) fn into_io(self) -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, self)
+    }
+
+    /**
+     * The time left before *deadline*, or Timeout::Total if it has passed.
+     */
+    pub(crate) fn remaining(deadline: Option<Instant>) -> Result<Option<Duration>, Timeout> {
+        match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Ok(Some(remaining)),
+                _ => Err(Timeout::Total),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+impl Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timeout::Connect => write!(f, "connect timeout"),
+            Timeout::Read => write!(f, "read timeout"),
+            Timeout::Total => write!(f, "total timeout"),
+        }
+    }
+}
+
+impl StdError for Timeout {}
+
+/**
+ * Whether an I/O error is a socket timing out, which is reported differently across platforms.
+ */
+pub(crate) fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
@@ -0,0 +1,123 @@
+use {
+    crate::{
+        serialize_chunk, serialize_crlf, serialize_last_chunk, Encoding, Error, Headers, CHUNK_SIZE,
+    },
+    cookie_factory::{gen, sequence::tuple},
+    std::{
+        fmt::{self, Debug},
+        io::{self, Read, Write},
+        sync::{Arc, Mutex},
+    },
+};
+
+/**
+ * A request body that is read from a source as it is sent, instead of being held in memory.
+ * It can only be sent once; clones of a request share the same source.
+ */
+#[derive(Clone)]
+pub struct BodyReader {
+    reader: Arc<Mutex<Option<Box<dyn Read + Send>>>>,
+    len: Option<u64>,
+}
+
+impl BodyReader {
+    /**
+     * Read the body from *reader*. Bodies of unknown *len* are sent with chunked transfer-encoding.
+     */
+    pub fn new(reader: Box<dyn Read + Send>, len: Option<u64>) -> Self {
+        Self {
+            reader: Arc::new(Mutex::new(Some(reader))),
+            len,
+        }
+    }
+
+    /**
+     * The length of the body, if it is known up front.
+     */
+    pub fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /**
+     * Whether the body is known to be empty.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.len == Some(0)
+    }
+
+    /**
+     * Compress the body with *encoding* as it is read. The compressed length is not known up front.
+     */
+    pub(crate) fn encode(&self, encoding: Encoding) -> Result<Self, Error> {
+        let reader = self.take()?;
+
+        Ok(Self::new(encoding.encode(reader)?, None))
+    }
+
+    /**
+     * Take the source of the body, failing with Error::BodyConsumed if it was already sent.
+     */
+    fn take(&self) -> Result<Box<dyn Read + Send>, Error> {
+        self.reader
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .ok_or(Error::BodyConsumed)
+    }
+
+    /**
+     * Write the body to *w*, as chunks followed by *trailers* if *chunked* is set.
+     * Fails with Error::BodyConsumed if the body was already sent.
+     */
+    pub(crate) fn write_to<W: Write>(
+        &self,
+        w: &mut W,
+        chunked: bool,
+        trailers: &Headers,
+    ) -> Result<(), Error> {
+        let mut reader = self.take()?;
+
+        if !chunked {
+            let len = self.len.unwrap_or(u64::MAX);
+            let sent = io::copy(&mut reader.take(len), w)?;
+
+            if self.len.is_some_and(|len| sent < len) {
+                let msg = format!("Body ended after {} of {} bytes.", sent, len);
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg).into());
+            }
+
+            return Ok(());
+        }
+
+        let mut buf = vec![0; CHUNK_SIZE];
+
+        loop {
+            let len = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            gen(serialize_chunk(&buf[..len]), &mut *w)?;
+        }
+
+        gen(
+            tuple((
+                serialize_last_chunk(),
+                trailers.serialize(),
+                serialize_crlf(),
+            )),
+            &mut *w,
+        )?;
+        Ok(())
+    }
+}
+
+impl Debug for BodyReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyReader")
+            .field("len", &self.len)
+            .finish()
+    }
+}
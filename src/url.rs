@@ -0,0 +1,350 @@
+use {
+    crate::{Error, Protocol},
+    std::{borrow::Cow, net::Ipv6Addr},
+};
+
+/**
+ * An absolute *http* or *https* URL, split into its components.
+ * Components borrow from the parsed string and are not percent-decoded.
+ */
+#[derive(Debug, PartialEq)]
+pub struct Url<'a> {
+    pub protocol: Protocol,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a str>,
+    // The host, with IPv6 literals kept in their brackets.
+    pub host: &'a str,
+    pub port: Option<u16>,
+    pub path: &'a str,
+    pub query: Option<&'a str>,
+    pub fragment: Option<&'a str>,
+}
+
+impl<'a> Url<'a> {
+    /**
+     * Parse an absolute URL of the form *scheme://[userinfo@]host[:port][/path][?query][#fragment]*.
+     */
+    pub fn parse(url: &'a str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidUrl(url.to_string());
+
+        let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+        let protocol = if scheme.eq_ignore_ascii_case("http") {
+            Protocol::HTTP
+        } else if scheme.eq_ignore_ascii_case("https") {
+            Protocol::HTTPS
+        } else {
+            return Err(Error::UnsupportedScheme(scheme.to_string()));
+        };
+
+        let (authority, rest) = rest.split_at(rest.find(['/', '?', '#']).unwrap_or(rest.len()));
+        let (rest, fragment) = match rest.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (rest, None),
+        };
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+
+        let (userinfo, hostport) = match authority.rsplit_once('@') {
+            Some((userinfo, hostport)) => (Some(userinfo), hostport),
+            None => (None, authority),
+        };
+        let (username, password) = match userinfo {
+            Some(userinfo) => match userinfo.split_once(':') {
+                Some((username, password)) => (Some(username), Some(password)),
+                None => (Some(userinfo), None),
+            },
+            None => (None, None),
+        };
+
+        let (host, port) = if hostport.starts_with('[') {
+            let end = hostport.find(']').ok_or_else(invalid)? + 1;
+            match &hostport[end..] {
+                "" => (&hostport[..end], None),
+                port => (
+                    &hostport[..end],
+                    Some(port.strip_prefix(':').ok_or_else(invalid)?),
+                ),
+            }
+        } else {
+            match hostport.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (hostport, None),
+            }
+        };
+
+        if !is_valid_host(host) {
+            return Err(invalid());
+        }
+
+        let port = match port {
+            // An empty port means the scheme's default.
+            Some("") | None => None,
+            Some(port) => Some(port.parse().map_err(|_| invalid())?),
+        };
+
+        Ok(Self {
+            protocol,
+            username,
+            password,
+            host,
+            port,
+            path,
+            query,
+            fragment,
+        })
+    }
+
+    /**
+     * The port to connect to: the explicit one, or the default for the scheme.
+     */
+    pub fn port_or_default(&self) -> u16 {
+        self.port.unwrap_or_else(|| self.protocol.default_port())
+    }
+
+    /**
+     * The value of the *HOST* header for this URL, which only includes non-default ports.
+     */
+    pub fn authority(&self) -> Cow<'a, str> {
+        match self.port {
+            Some(port) if port != self.protocol.default_port() => {
+                Cow::Owned(format!("{}:{}", self.host, port))
+            }
+            _ => Cow::Borrowed(self.host),
+        }
+    }
+
+    /**
+     * Resolve a (possibly relative) reference, such as a *LOCATION* header, against this URL.
+     */
+    pub fn join(&self, reference: &str) -> String {
+        let scheme = self.protocol.scheme();
+        let has_scheme = reference
+            .find(':')
+            .map(|end| &reference[..end])
+            .is_some_and(|scheme| {
+                scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+            });
+
+        if has_scheme {
+            reference.to_string()
+        } else if reference.starts_with("//") {
+            format!("{}:{}", scheme, reference)
+        } else if reference.starts_with('/') {
+            let target = remove_dot_segments(reference);
+            format!("{}://{}{}", scheme, self.authority(), target)
+        } else if reference.is_empty() || reference.starts_with('#') {
+            format!("{}://{}{}", scheme, self.authority(), self.target())
+        } else if reference.starts_with('?') {
+            let path = if self.path.is_empty() { "/" } else { self.path };
+            format!("{}://{}{}{}", scheme, self.authority(), path, reference)
+        } else {
+            let dir = &self.path[..self.path.rfind('/').map_or(0, |end| end + 1)];
+            let dir = if dir.is_empty() { "/" } else { dir };
+            let target = remove_dot_segments(&format!("{}{}", dir, reference));
+            format!("{}://{}{}", scheme, self.authority(), target)
+        }
+    }
+
+    /**
+     * The request target sent on the request line: the path and query, without the fragment.
+     */
+    pub fn target(&self) -> Cow<'a, str> {
+        match (self.path, self.query) {
+            ("", None) => Cow::Borrowed("/"),
+            (path, None) => Cow::Borrowed(path),
+            ("", Some(query)) => Cow::Owned(format!("/?{}", query)),
+            (path, Some(query)) => Cow::Owned(format!("{}?{}", path, query)),
+        }
+    }
+}
+
+/**
+ * Whether *host* is a bracketed IPv6 literal, or a registered name or IPv4 address, per RFC 3986 section 3.2.2.
+ * Anything else, such as whitespace or line breaks that would end up in the *HOST* header, is rejected.
+ */
+fn is_valid_host(host: &str) -> bool {
+    if let Some(literal) = host.strip_prefix('[') {
+        return literal
+            .strip_suffix(']')
+            .is_some_and(|ip| ip.parse::<Ipv6Addr>().is_ok());
+    }
+
+    let bytes = host.as_bytes();
+
+    !bytes.is_empty()
+        && bytes.iter().enumerate().all(|(i, &c)| match c {
+            b'%' => bytes
+                .get(i + 1..i + 3)
+                .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)),
+            c => c.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=".contains(&c),
+        })
+}
+
+/**
+ * Remove the *.* and *..* segments from the path of *target*, per RFC 3986 section 5.2.4,
+ * keeping any query or fragment after it untouched. The path must be absolute.
+ */
+fn remove_dot_segments(target: &str) -> String {
+    let (path, rest) = target.split_at(target.find(['?', '#']).unwrap_or(target.len()));
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    let mut output = Vec::with_capacity(segments.len());
+
+    for (i, &segment) in segments.iter().enumerate() {
+        match segment {
+            "." => {}
+            ".." => {
+                output.pop();
+            }
+            segment => output.push(segment),
+        }
+
+        // A trailing dot segment still refers to a directory.
+        if i == segments.len() - 1 && (segment == "." || segment == "..") {
+            output.push("");
+        }
+    }
+
+    format!("/{}{}", output.join("/"), rest)
+}
+
+/**
+ * Encode every byte of *s* as a *%XX* escape, except the unreserved characters of RFC 3986 section 2.3.
+ */
+pub fn percent_encode(s: &str) -> String {
+    percent_encode_except(s, |c| c.is_ascii_alphanumeric() || b"-._~".contains(&c))
+}
+
+/**
+ * Encode every byte of *s* for which *keep* is false as a *%XX* escape.
+ */
+pub(crate) fn percent_encode_except(s: &str, keep: impl Fn(u8) -> bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+
+    for &c in s.as_bytes() {
+        match keep(c) {
+            true => encoded.push(c as char),
+            false => encoded.push_str(&format!("%{:02X}", c)),
+        }
+    }
+
+    encoded
+}
+
+/**
+ * Decode *%XX* escapes. Invalid escapes are kept as-is, and invalid UTF-8 is replaced.
+ */
+pub fn percent_decode(s: &str) -> Cow<'_, str> {
+    if !s.contains('%') {
+        return Cow::Borrowed(s);
+    }
+
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = match bytes.get(i + 1..i + 3) {
+            Some(hex) if bytes[i] == b'%' => std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+#[test]
+fn test_parse_url() -> Result<(), Error> {
+    let url = Url::parse("https://user:p%40ss@[::1]:8443/a/b?x=1&y=2#top")?;
+
+    assert_eq!(url.protocol, Protocol::HTTPS);
+    assert_eq!(url.username, Some("user"));
+    assert_eq!(url.password.map(percent_decode).as_deref(), Some("p@ss"));
+    assert_eq!(url.host, "[::1]");
+    assert_eq!(url.port_or_default(), 8443);
+    assert_eq!(url.authority(), "[::1]:8443");
+    assert_eq!(url.target(), "/a/b?x=1&y=2");
+    assert_eq!(url.fragment, Some("top"));
+
+    let url = Url::parse("HTTP://example.com?q")?;
+
+    assert_eq!(url.port_or_default(), 80);
+    assert_eq!(url.authority(), "example.com");
+    assert_eq!(url.target(), "/?q");
+
+    assert!(matches!(
+        Url::parse("ftp://example.com/"),
+        Err(Error::UnsupportedScheme(_))
+    ));
+    assert!(matches!(
+        Url::parse("example.com/"),
+        Err(Error::InvalidUrl(_))
+    ));
+    assert!(matches!(
+        Url::parse("http://example.com:99999/"),
+        Err(Error::InvalidUrl(_))
+    ));
+    assert!(matches!(
+        Url::parse("http:///path"),
+        Err(Error::InvalidUrl(_))
+    ));
+
+    for url in &[
+        "http://evil.com\r\nX-Injected=1/path",
+        "http://a b/",
+        "http://[::1/",
+        "http://[not-ipv6]/",
+        "http://bad%zzhost/",
+    ] {
+        assert!(
+            matches!(Url::parse(url), Err(Error::InvalidUrl(_))),
+            "{}",
+            url
+        );
+    }
+    assert_eq!(
+        Url::parse("http://caf%C3%A9.example/")?.host,
+        "caf%C3%A9.example"
+    );
+    assert_eq!(Url::parse("http://10.0.0.1:8080")?.host, "10.0.0.1");
+
+    Ok(())
+}
+
+#[test]
+fn test_join_url() -> Result<(), Error> {
+    let base = Url::parse("https://example.com:8443/a/b?x=1")?;
+
+    assert_eq!(base.join("http://other.org/"), "http://other.org/");
+    assert_eq!(base.join("//cdn.org/c"), "https://cdn.org/c");
+    assert_eq!(base.join("/c?y=2"), "https://example.com:8443/c?y=2");
+    assert_eq!(base.join("c"), "https://example.com:8443/a/c");
+    assert_eq!(base.join("?y=2"), "https://example.com:8443/a/b?y=2");
+    assert_eq!(base.join(""), "https://example.com:8443/a/b?x=1");
+    assert_eq!(base.join("../x"), "https://example.com:8443/x");
+    assert_eq!(base.join("./c/."), "https://example.com:8443/a/c/");
+    assert_eq!(
+        base.join("../../../c/..?y=../"),
+        "https://example.com:8443/?y=../"
+    );
+    assert_eq!(base.join("/c/./d/../e"), "https://example.com:8443/c/e");
+
+    Ok(())
+}